@@ -0,0 +1,186 @@
+use ahash::AHashSet;
+use crate::game::Game;
+use crate::methods::Search;
+use crate::point::Point;
+use crate::set::Set;
+use crate::solvers::SolverConfig;
+use std::cmp::Reverse;
+use std::time::Instant;
+
+// Scores a position for the beam. Higher is better.
+pub type Evaluation = fn(&Game) -> usize;
+
+#[derive(Copy, Clone, Debug)]
+pub struct BeamConfig {
+    // Number of positions kept at each depth, at least one.
+    pub width: usize,
+    pub evaluation: Evaluation,
+}
+
+impl Default for BeamConfig {
+    fn default() -> Self {
+        BeamConfig {
+            width: 100,
+            evaluation: Game::possible_moves,
+        }
+    }
+}
+
+// What happened to the beam on its way to positions with `depth` sets.
+#[derive(Copy, Clone, Debug)]
+pub struct BeamDepth {
+    pub depth: usize,
+    // Distinct positions reached from the previous beam.
+    pub candidates: usize,
+    // Positions dropped for being reachable from more than one position of the previous beam.
+    pub duplicates: usize,
+    pub kept: usize,
+    // Evaluations of the best and worst kept positions.
+    pub best: usize,
+    pub worst: usize,
+}
+
+// Expands every position of the beam by every legal move, and keeps the `width` best of the
+// results by `evaluation` until no moves are left. Like the gamestates of the DFS, a position is
+// its sets in any order, so positions reached by playing the same sets in a different order are
+// only kept once. The width has to be at least one.
+pub fn beam(start: Game, beam: BeamConfig, config: SolverConfig) -> (Search, Vec<BeamDepth>) {
+    assert!(beam.width > 0, "The beam must keep at least one position.");
+    let mut search = Search::new(&start, &config, Instant::now());
+    let mut depths = Vec::new();
+    let mut games = vec![start];
+    loop {
+        if search.stopped() {
+            break;
+        }
+        let mut seen: AHashSet<Vec<Set>> = AHashSet::new();
+        let mut duplicates = 0;
+        // (evaluation, index of the parent in `games`, move)
+        let mut candidates: Vec<(usize, usize, Set, Point)> = Vec::new();
+        let mut stopped = false;
+        'expand: for (parent, game) in games.iter_mut().enumerate() {
+            for (set, point) in game.legal_moves() {
+                game.add_set(set, point);
+                search.count_node();
+                if search.stopped() {
+                    // Every position at this depth has the same score, so the one just reached is
+                    // as good as any candidate left unexpanded.
+                    search.record(game);
+                    game.remove_set(set, point);
+                    stopped = true;
+                    break 'expand;
+                }
+                let mut sets = game.sets.clone();
+                sets.sort_unstable_by_key(Set::packed);
+                if seen.insert(sets) {
+                    candidates.push(((beam.evaluation)(game), parent, set, point));
+                } else {
+                    duplicates += 1;
+                }
+                game.remove_set(set, point);
+            }
+        }
+        if stopped || candidates.is_empty() {
+            break;
+        }
+        // Stable, so ties keep the order moves were generated in and runs are reproducible.
+        candidates.sort_by_key(|&(evaluation, ..)| Reverse(evaluation));
+        let total = candidates.len();
+        candidates.truncate(beam.width);
+        games = candidates
+            .iter()
+            .map(|&(_, parent, set, point)| {
+                let mut game = games[parent].clone();
+                game.add_set(set, point);
+                game
+            })
+            .collect();
+        depths.push(BeamDepth {
+            depth: games[0].score(),
+            candidates: total,
+            duplicates,
+            kept: games.len(),
+            best: candidates[0].0,
+            worst: candidates[candidates.len() - 1].0,
+        });
+        search.record(&games[0]);
+    }
+    (search.finish(), depths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::budget::Termination;
+    use crate::fixtures::assert_best_replays;
+
+    fn run(width: usize, target: Option<usize>) -> (Search, Vec<BeamDepth>) {
+        let config = BeamConfig { width, ..BeamConfig::default() };
+        beam(Game::new(), config, SolverConfig { target, ..SolverConfig::default() })
+    }
+
+    #[test]
+    fn duplicates_count_transpositions() {
+        // Wide enough to keep every position after the first move, and stopping after the second.
+        let (_, depths) = run(1000, Some(2));
+        let start = Game::new();
+        let mut positions: AHashSet<Vec<Set>> = AHashSet::new();
+        let mut played = 0;
+        for (first, point) in start.legal_moves() {
+            let mut game = start.clone();
+            game.add_set(first, point);
+            for (second, point) in game.legal_moves() {
+                game.add_set(second, point);
+                let mut sets = game.sets.clone();
+                sets.sort_unstable_by_key(Set::packed);
+                positions.insert(sets);
+                played += 1;
+                game.remove_set(second, point);
+            }
+        }
+        let second = depths[1];
+        assert_eq!(depths[0].kept, start.possible_moves());
+        assert_eq!(depths.len(), 2);
+        assert_eq!(second.depth, 2);
+        assert_eq!(second.candidates, positions.len());
+        assert_eq!(second.duplicates, played - positions.len());
+        assert!(second.duplicates > 0);
+    }
+
+    #[test]
+    fn keeps_width_best() {
+        let width = 5;
+        let (search, depths) = run(width, None);
+        assert_eq!(depths.len(), search.best().score());
+        for (i, depth) in depths.iter().enumerate() {
+            assert_eq!(depth.depth, i + 1);
+            assert_eq!(depth.kept, depth.candidates.min(width));
+            assert!(depth.best >= depth.worst);
+        }
+        assert!(depths.iter().any(|depth| depth.candidates > width));
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one position")]
+    fn rejects_zero_width() {
+        run(0, None);
+    }
+
+    #[test]
+    fn stops_within_node_limit() {
+        let limit = 1000;
+        let beam_config = BeamConfig { width: 200, ..BeamConfig::default() };
+        let config = SolverConfig { node_limit: Some(limit), ..SolverConfig::default() };
+        let (search, _) = beam(Game::new(), beam_config, config);
+        assert_eq!(search.termination(), Termination::NodeLimit);
+        assert!(search.nodes() <= limit, "{} nodes", search.nodes());
+        // The limit ran out part way through the third depth.
+        assert_eq!(search.best().score(), 3);
+    }
+
+    #[test]
+    fn best_game_replays() {
+        let beam_config = BeamConfig { width: 20, ..BeamConfig::default() };
+        assert_best_replays(|start| beam(start, beam_config, SolverConfig::default()).0);
+    }
+}
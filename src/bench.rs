@@ -1,21 +1,8 @@
-#![allow(dead_code)]
-
 #[macro_use] extern crate criterion;
 
-mod extras;
-mod game;
-mod methods;
-mod point;
-mod set;
-mod solvers;
-
-use ahash::AHashSet;
 use criterion::{black_box, Criterion};
-use game::{Game, STARTING_POINTS};
-use methods::{
-    base,
-    base_highest_set,
-    base_highest_set_vec,
+use dots::{Game, Gamestate, Point, Set, Storage};
+use dots::methods::{
     cmp_hpm,
     cmp_mnx_hpm,
     cmp_quad_hpm,
@@ -28,51 +15,58 @@ use methods::{
     v_eq,
     v_hs_eq
 };
-use point::Point;
-use set::{Direction, Set};
-use solvers::multithreaded_method;
+use dots::SolverConfig;
+use dots::solvers::singlethreaded_method;
 
-pub const DESIRED_SCORE: usize = 30;
+// Positions played by each run. Without a target the search keeps backtracking for a better game
+// until it runs out, so every run does the same amount of real search on one thread whatever the
+// storage.
+const NODES: usize = 20_000;
+const CONFIG: SolverConfig = SolverConfig {
+    target: None,
+    threads: None,
+    cancel: None,
+    progress: None,
+    checkpoint: None,
+    time_limit: None,
+    node_limit: Some(NODES)
+};
 
-const POINT: Point = STARTING_POINTS[0];
+type MoveInfo = (usize, Set, Point);
 
 fn bench_method(c: &mut Criterion) {
-    c.bench_function(format!("[{}] method (set hpm)", DESIRED_SCORE).as_str(), move |b| {
-        let method = Method::new(v_hs_eq, store_hs, store_npm_s_p, cmp_hpm, spm_intoiter_rev);
-        b.iter(|| {
-            black_box(multithreaded_method(method))
-        });
-    });
-    c.bench_function(format!("[{}] method (set mnx -> hpm)", DESIRED_SCORE).as_str(), move |b| {
-        let method = Method::new(v_hs_eq, store_hs, store_npm_s_p, cmp_mnx_hpm, spm_intoiter_rev);
-        b.iter(|| {
-            black_box(multithreaded_method(method))
-        });
-    });
-    c.bench_function(format!("[{}] method (set quad -> hpm)", DESIRED_SCORE).as_str(), move |b| {
-        let method = Method::new(v_hs_eq, store_hs, store_npm_s_p, cmp_quad_hpm, spm_intoiter);
-        b.iter(|| {
-            black_box(multithreaded_method(method))
-        });
-    });
-    c.bench_function(format!("[{}] method (vec hpm)", DESIRED_SCORE).as_str(), move |b| {
-        let method = Method::new(v_eq, store_v, store_npm_s_p, cmp_hpm, spm_intoiter_rev);
-        b.iter(|| {
-            black_box(multithreaded_method(method))
-        });
-    });
-    c.bench_function(format!("[{}] method (vec mnx -> hpm)", DESIRED_SCORE).as_str(), move |b| {
-        let method = Method::new(v_eq, store_v, store_npm_s_p, cmp_mnx_hpm, spm_intoiter_rev);
-        b.iter(|| {
-            black_box(multithreaded_method(method))
-        });
-    });
-    c.bench_function(format!("[{}] method (vec quad -> hpm)", DESIRED_SCORE).as_str(), move |b| {
-        let method = Method::new(v_eq, store_v, store_npm_s_p, cmp_quad_hpm, spm_intoiter);
-        b.iter(|| {
-            black_box(multithreaded_method(method))
+    let method = Method::new(v_hs_eq, store_hs, store_npm_s_p, cmp_hpm, spm_intoiter_rev);
+    bench_storages(c, "set hpm", method);
+    let method = Method::new(v_hs_eq, store_hs, store_npm_s_p, cmp_mnx_hpm, spm_intoiter_rev);
+    bench_storages(c, "set mnx -> hpm", method);
+    let method = Method::new(v_hs_eq, store_hs, store_npm_s_p, cmp_quad_hpm, spm_intoiter);
+    bench_storages(c, "set quad -> hpm", method);
+    let method = Method::new(v_eq, store_v, store_npm_s_p, cmp_hpm, spm_intoiter_rev);
+    bench_storages(c, "vec hpm", method);
+    let method = Method::new(v_eq, store_v, store_npm_s_p, cmp_mnx_hpm, spm_intoiter_rev);
+    bench_storages(c, "vec mnx -> hpm", method);
+    let method = Method::new(v_eq, store_v, store_npm_s_p, cmp_quad_hpm, spm_intoiter);
+    bench_storages(c, "vec quad -> hpm", method);
+}
+
+// Runs `method` on the grid and on the hash map storage of `Game`.
+fn bench_storages<MoveSet, MoveIter>(
+    c: &mut Criterion,
+    name: &str,
+    method: Method<MoveSet, MoveInfo, MoveIter>
+)
+    where
+        MoveSet: 'static + Clone + Send + Gamestate,
+        MoveIter: 'static + Iterator<Item = (Set, Point)>
+{
+    for &storage in [Storage::Grid, Storage::HashMap].iter() {
+        let id = format!("[{} nodes] method ({}, {:?})", NODES, name, storage);
+        c.bench_function(id.as_str(), move |b| {
+            b.iter(|| {
+                black_box(singlethreaded_method(method, Game::new().with_storage(storage), CONFIG))
+            });
         });
-    });
+    }
 }
 
 criterion_group! {
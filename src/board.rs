@@ -0,0 +1,182 @@
+use ahash::AHashMap;
+use crate::point::Point;
+
+// How a `Game` stores the flags of its points.
+#[derive(Eq, PartialEq, Copy, Clone, Hash, Debug, Default)]
+pub enum Storage {
+    // A hash map from point to flags.
+    HashMap,
+    // A dense array of flags over a window around the points, grown when a point lands outside it.
+    #[default]
+    Grid,
+}
+
+// Points of a game with their flags, behind the handful of map operations `Game` needs.
+#[derive(Clone, Debug)]
+pub(crate) enum Board {
+    HashMap(AHashMap<Point, u8>),
+    Grid(Grid),
+}
+
+impl Board {
+    pub(crate) fn new(storage: Storage) -> Self {
+        match storage {
+            Storage::HashMap => Board::HashMap(AHashMap::new()),
+            Storage::Grid => Board::Grid(Grid::new()),
+        }
+    }
+
+    pub(crate) fn storage(&self) -> Storage {
+        match self {
+            Board::HashMap(_) => Storage::HashMap,
+            Board::Grid(_) => Storage::Grid,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn get(&self, point: &Point) -> Option<&u8> {
+        match self {
+            Board::HashMap(map) => map.get(point),
+            Board::Grid(grid) => grid.get(point),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn get_mut(&mut self, point: &Point) -> Option<&mut u8> {
+        match self {
+            Board::HashMap(map) => map.get_mut(point),
+            Board::Grid(grid) => grid.get_mut(point),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn contains_key(&self, point: &Point) -> bool {
+        self.get(point).is_some()
+    }
+
+    #[inline]
+    pub(crate) fn insert(&mut self, point: Point, flags: u8) -> Option<u8> {
+        match self {
+            Board::HashMap(map) => map.insert(point, flags),
+            Board::Grid(grid) => grid.insert(point, flags),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn remove(&mut self, point: &Point) -> Option<u8> {
+        match self {
+            Board::HashMap(map) => map.remove(point),
+            Board::Grid(grid) => grid.remove(point),
+        }
+    }
+
+    // Points and their flags, in no particular order.
+    pub(crate) fn iter(&self) -> Box<dyn Iterator<Item = (Point, u8)> + '_> {
+        match self {
+            Board::HashMap(map) => Box::new(map.iter().map(|(&point, &flags)| (point, flags))),
+            Board::Grid(grid) => Box::new(grid.iter()),
+        }
+    }
+}
+
+// Space left around the points when the window is grown, so that it doesn't have to grow again
+// for the next few moves.
+const MARGIN: i16 = 8;
+
+#[derive(Clone, Debug)]
+pub(crate) struct Grid {
+    left: i16,
+    bottom: i16,
+    width: i16,
+    height: i16,
+    // Row by row from the bottom left corner of the window. `None` where there's no point.
+    cells: Vec<Option<u8>>,
+}
+
+impl Grid {
+    fn new() -> Self {
+        Grid {
+            left: 0,
+            bottom: 0,
+            width: 0,
+            height: 0,
+            cells: Vec::new(),
+        }
+    }
+
+    #[inline]
+    fn index(&self, point: &Point) -> Option<usize> {
+        let x = point.x - self.left;
+        let y = point.y - self.bottom;
+        if x >= 0 && y >= 0 && x < self.width && y < self.height {
+            Some(y as usize * self.width as usize + x as usize)
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn get(&self, point: &Point) -> Option<&u8> {
+        self.index(point).and_then(|i| self.cells[i].as_ref())
+    }
+
+    #[inline]
+    fn get_mut(&mut self, point: &Point) -> Option<&mut u8> {
+        let i = self.index(point)?;
+        self.cells[i].as_mut()
+    }
+
+    #[inline]
+    fn insert(&mut self, point: Point, flags: u8) -> Option<u8> {
+        let i = match self.index(&point) {
+            Some(i) => i,
+            None => {
+                self.grow(point);
+                self.index(&point).unwrap()
+            },
+        };
+        self.cells[i].replace(flags)
+    }
+
+    #[inline]
+    fn remove(&mut self, point: &Point) -> Option<u8> {
+        let i = self.index(point)?;
+        self.cells[i].take()
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (Point, u8)> + '_ {
+        let (left, bottom, width) = (self.left, self.bottom, self.width as usize);
+        self.cells.iter().enumerate().filter_map(move |(i, cell)| {
+            cell.map(|flags| {
+                (Point::new(left + (i % width) as i16, bottom + (i / width) as i16), flags)
+            })
+        })
+    }
+
+    // Moves the window so that it covers `point` with `MARGIN` to spare, keeping every point
+    // already in it.
+    fn grow(&mut self, point: Point) {
+        let (left, bottom, right, top) = if self.cells.is_empty() {
+            (point.x - MARGIN, point.y - MARGIN, point.x + MARGIN, point.y + MARGIN)
+        } else {
+            (
+                self.left.min(point.x - MARGIN),
+                self.bottom.min(point.y - MARGIN),
+                (self.left + self.width - 1).max(point.x + MARGIN),
+                (self.bottom + self.height - 1).max(point.y + MARGIN),
+            )
+        };
+        let mut grown = Grid {
+            left,
+            bottom,
+            width: right - left + 1,
+            height: top - bottom + 1,
+            cells: vec![None; (right - left + 1) as usize * (top - bottom + 1) as usize],
+        };
+        for (point, flags) in self.iter() {
+            let i = grown.index(&point).unwrap();
+            grown.cells[i] = Some(flags);
+        }
+        *self = grown;
+    }
+}
@@ -0,0 +1,139 @@
+use crate::solvers::SolverConfig;
+use std::fmt::{self, Display};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+// Why a solver returned, from `Search::termination`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum Termination {
+    // The best game reached the target.
+    Reached,
+    // The solver ran to the end: every root was searched, or every iteration was done.
+    #[default]
+    Exhausted,
+    // `SolverConfig::cancel` was cancelled.
+    Cancelled,
+    // `SolverConfig::time_limit` ran out.
+    TimeLimit,
+    // `SolverConfig::node_limit` positions were played.
+    NodeLimit,
+}
+
+impl Display for Termination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            Termination::Reached => "reached the target",
+            Termination::Exhausted => "searched everything",
+            Termination::Cancelled => "cancelled",
+            Termination::TimeLimit => "ran out of time",
+            Termination::NodeLimit => "ran out of nodes",
+        };
+        write!(f, "{}", reason)
+    }
+}
+
+// The time and node limits of a search, shared by all of its workers.
+#[derive(Debug)]
+pub(crate) struct Budget {
+    deadline: Option<Instant>,
+    nodes: Option<usize>,
+    spent: AtomicUsize,
+    // The limit that was hit first.
+    exceeded: OnceLock<Termination>,
+}
+
+impl Budget {
+    // `None` when the config sets no limits. Both limits count from now, so a resumed search gets
+    // them in full again. A time limit too far off to be an `Instant` is no limit at all, and a
+    // node limit of zero has run out before the first position.
+    pub(crate) fn new(config: &SolverConfig) -> Option<Self> {
+        if config.time_limit.is_none() && config.node_limit.is_none() {
+            return None;
+        }
+        let exceeded = OnceLock::new();
+        if config.node_limit == Some(0) {
+            let _ = exceeded.set(Termination::NodeLimit);
+        }
+        Some(Budget {
+            deadline: config.time_limit.and_then(|limit| Instant::now().checked_add(limit)),
+            nodes: config.node_limit,
+            spent: AtomicUsize::new(0),
+            exceeded,
+        })
+    }
+
+    // Counts a position played by any worker.
+    #[inline]
+    pub(crate) fn spend(&self) {
+        if let Some(nodes) = self.nodes {
+            if self.spent.fetch_add(1, Ordering::Relaxed) + 1 >= nodes {
+                let _ = self.exceeded.set(Termination::NodeLimit);
+            }
+        }
+    }
+
+    // Whether the search has to stop now.
+    #[inline]
+    pub(crate) fn exhausted(&self) -> bool {
+        if self.exceeded.get().is_some() {
+            return true;
+        }
+        if self.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            let _ = self.exceeded.set(Termination::TimeLimit);
+            return true;
+        }
+        false
+    }
+
+    // The limit the search stopped for, if it did.
+    pub(crate) fn exceeded(&self) -> Option<Termination> {
+        self.exceeded.get().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::beam::{beam, BeamConfig};
+    use crate::game::Game;
+    use crate::mcts::{mcts, MctsConfig};
+    use crate::methods::{
+        cmp_hpm,
+        Method,
+        spm_intoiter_rev,
+        store_hs,
+        store_npm_s_p,
+        v_hs_eq
+    };
+    use crate::nmcs::nmcs;
+    use crate::nrpa::{nrpa, NrpaConfig};
+    use crate::solvers::singlethreaded_method;
+    use std::time::Duration;
+
+    #[test]
+    fn time_limit_past_any_instant_never_runs_out() {
+        let config = SolverConfig { time_limit: Some(Duration::MAX), ..SolverConfig::default() };
+        let budget = Budget::new(&config).unwrap();
+        assert!(!budget.exhausted());
+        assert_eq!(budget.exceeded(), None);
+    }
+
+    #[test]
+    fn zero_node_limit_plays_nothing() {
+        let config = SolverConfig { node_limit: Some(0), ..SolverConfig::default() };
+        let method = Method::new(v_hs_eq, store_hs, store_npm_s_p, cmp_hpm, spm_intoiter_rev);
+        let searches = vec![
+            singlethreaded_method(method, Game::new(), config.clone()),
+            beam(Game::new(), BeamConfig::default(), config.clone()).0,
+            nmcs(Game::new(), 1, 0, config.clone()),
+            nrpa(Game::new(), NrpaConfig::default(), config.clone()),
+            mcts(Game::new(), MctsConfig::default(), config),
+        ];
+        for search in searches {
+            assert_eq!(search.termination(), Termination::NodeLimit);
+            assert_eq!(search.nodes(), 0);
+            assert_eq!(search.best().score(), 0);
+        }
+    }
+}
@@ -1,5 +1,4 @@
 use crate::game::Game;
-use std::i16::{MAX as I16MAX, MIN as I16MIN};
 use svg::Document;
 use svg::node::element::Circle;
 use svg::node::element::Line;
@@ -9,11 +8,11 @@ const RADIUS: i32 = 5;
 const STROKE: i32 = 3;
 
 pub fn display_game_as_svg(filename: &str, game: &Game) {
-	let mut x_min = I16MAX as i32;
-	let mut x_max = I16MIN as i32;
+	let mut x_min = i16::MAX as i32;
+	let mut x_max = i16::MIN as i32;
 	let mut y_min = x_min;
 	let mut y_max = x_max;
-	for point in game.points.keys() {
+	for point in game.points() {
 		x_min = x_min.min(point.x as i32);
 		y_min = y_min.min(point.y as i32);
 		x_max = x_max.max(point.x as i32);
@@ -29,7 +28,7 @@ pub fn display_game_as_svg(filename: &str, game: &Game) {
 		.set("height", (y_max - y_min) * SPACING + 2 * RADIUS)
 		.set("x", 0)
 		.set("y", 0);
-	for point in game.points.keys() {
+	for point in game.points() {
 		let circle = Circle::new()
 			.set("cx", (point.x as i32 - x_min) * SPACING + RADIUS)
 			.set("cy", (point.y as i32 - y_min) * SPACING + RADIUS)
@@ -38,7 +37,7 @@ pub fn display_game_as_svg(filename: &str, game: &Game) {
 		document = document.add(circle);
 	}
 	for set in game.sets.iter() {
-		let (x, y) = set.direction.full_step();
+		let (x, y) = set.direction.full_step(set.length);
 		let (x, y) = (x as i32, y as i32);
 		let line = Line::new()
 			.set("x1", (set.start_x as i32 - x_min) * SPACING + RADIUS)
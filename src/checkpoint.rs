@@ -0,0 +1,432 @@
+use ahash::AHashSet;
+use crate::game::Game;
+use crate::methods::{RootOutcome, Search, TranspositionTable};
+use crate::point::Point;
+use crate::rules::Rules;
+use crate::set::Set;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{rename, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::iter::once;
+use std::path::{Path, PathBuf};
+use std::sync::{Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+// The formats a transposition table can store positions in, which checkpoints write out as the
+// sets of each stored position.
+pub trait Gamestate: Sized {
+    // Name of the format. Checkpoints record it, so that a table is only read back into the format
+    // it was written from.
+    const STORE: &'static str;
+
+    fn to_sets(&self) -> Vec<Set>;
+    fn from_sets(sets: Vec<Set>) -> Self;
+}
+
+impl Gamestate for AHashSet<Set> {
+    const STORE: &'static str = "hash set";
+
+    fn to_sets(&self) -> Vec<Set> {
+        self.iter().cloned().collect()
+    }
+
+    fn from_sets(sets: Vec<Set>) -> Self {
+        sets.into_iter().collect()
+    }
+}
+
+impl Gamestate for Vec<Set> {
+    const STORE: &'static str = "vec";
+
+    fn to_sets(&self) -> Vec<Set> {
+        self.clone()
+    }
+
+    fn from_sets(sets: Vec<Set>) -> Self {
+        sets
+    }
+}
+
+// Where the depth-first solvers that support it save their state, and how often.
+#[derive(Clone, Debug)]
+pub struct Checkpointing {
+    pub path: PathBuf,
+    pub interval: Duration,
+}
+
+// Everything a depth-first search needs to carry on from where it was saved, so that resuming it
+// gives the same result as never having stopped.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Checkpoint {
+    starting_points: Vec<Point>,
+    rules: Rules,
+    // Sets already played in the start.
+    start: Vec<Set>,
+    // The `Gamestate::STORE` of the transposition tables.
+    store: String,
+    elapsed: Duration,
+    // Results of the roots that were finished.
+    pub(crate) finished: SearchState,
+    // Roots that nobody had started.
+    pub(crate) roots: Vec<Point>,
+    pub(crate) workers: Vec<WorkerState>,
+}
+
+impl Checkpoint {
+    // A search of `start` that hasn't done anything yet.
+    pub(crate) fn new<MoveSet: Gamestate>(start: &Game) -> Self {
+        let roots = start.root_points();
+        let finished = SearchState {
+            skipped_roots: start.starting_points().len() - roots.len(),
+            ..SearchState::default()
+        };
+        Checkpoint {
+            starting_points: start.starting_points().to_vec(),
+            rules: start.rules(),
+            start: start.sets().to_vec(),
+            store: MoveSet::STORE.to_owned(),
+            elapsed: Duration::default(),
+            finished,
+            roots,
+            workers: Vec::new(),
+        }
+    }
+
+    // The state of a search of `start` as it is now, made of the search of every finished root
+    // and the state of every worker, whose tables are stored as `store`.
+    pub(crate) fn of(
+        start: &Game,
+        store: &str,
+        finished: &Search,
+        roots: Vec<Point>,
+        workers: Vec<WorkerState>
+    ) -> Self {
+        Checkpoint {
+            starting_points: start.starting_points().to_vec(),
+            rules: start.rules(),
+            start: start.sets().to_vec(),
+            store: store.to_owned(),
+            elapsed: finished.started.elapsed(),
+            finished: finished.save(start, start),
+            roots,
+            workers,
+        }
+    }
+
+    // Fails with `io::ErrorKind::InvalidData` unless the file holds a checkpoint whose start and
+    // saved games can all be played out again, as resuming it does.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        let checkpoint: Checkpoint = bincode::deserialize_from(reader)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        checkpoint.check().map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        Ok(checkpoint)
+    }
+
+    // Checks what deserializing doesn't: rules that `Rules::new` accepts, and games that replay.
+    fn check(&self) -> Result<(), String> {
+        if self.rules.line_length < Rules::MIN_LINE_LENGTH {
+            return Err(format!("checkpoint has sets of {} points", self.rules.line_length));
+        }
+        let start = Game::from_points(self.starting_points.iter().cloned(), self.rules)
+            .replay(self.start.iter().cloned())
+            .map_err(|error| format!("saved start can't be replayed: {}", error))?;
+        let states = once(&self.finished)
+            .chain(self.workers.iter().filter_map(|worker| worker.search.as_ref()));
+        for state in states {
+            state.check(&start)?;
+        }
+        Ok(())
+    }
+
+    // Writes to a temporary file first, so that a crash while saving leaves the previous
+    // checkpoint as it was.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        let mut writer = BufWriter::new(File::create(&temporary)?);
+        bincode::serialize_into(&mut writer, self)
+            .map_err(io::Error::other)?;
+        writer.flush()?;
+        rename(&temporary, path)
+    }
+
+    // The game the saved search started from, which `load` has checked replays.
+    pub fn start(&self) -> Game {
+        Game::from_points(self.starting_points.iter().cloned(), self.rules)
+            .replay(self.start.iter().cloned())
+            .expect("Saved start can't be replayed.")
+    }
+
+    // The `Gamestate::STORE` the saved search kept its positions in.
+    pub fn store(&self) -> &str {
+        &self.store
+    }
+
+    // Fails unless the saved tables can be read back as `MoveSet`. Any other store would read
+    // them as positions that never match, and quietly lose them.
+    pub(crate) fn check_store<MoveSet: Gamestate>(&self) -> io::Result<()> {
+        if self.store == MoveSet::STORE {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "checkpoint was saved with the {} store, not the {} store",
+                    self.store,
+                    MoveSet::STORE
+                )
+            ))
+        }
+    }
+
+    // Starts the progress reports of `search`, the overall search resuming this one, off from the
+    // counts saved for the finished roots and every worker.
+    pub(crate) fn resume_progress(&self, search: &Search) {
+        if let Some(tally) = &search.progress {
+            let start = self.start.len();
+            let states = once(&self.finished)
+                .chain(self.workers.iter().filter_map(|worker| worker.search.as_ref()));
+            let (mut nodes, mut lookups, mut hits, mut best) = (0, 0, 0, 0);
+            for state in states {
+                nodes += state.nodes;
+                lookups += state.lookups;
+                hits += state.hits;
+                best = best.max(start + state.best.len());
+            }
+            tally.resume(nodes, lookups, hits, best);
+        }
+    }
+
+    // When the saved search started, as if it had never stopped.
+    pub(crate) fn started(&self) -> Instant {
+        Instant::now().checked_sub(self.elapsed).unwrap_or_else(Instant::now)
+    }
+}
+
+// A worker's transposition table, and the search of the root it was in the middle of, if any.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub(crate) struct WorkerState {
+    pub(crate) gamestates: Vec<(u64, Vec<Vec<Set>>)>,
+    pub(crate) search: Option<SearchState>,
+}
+
+impl WorkerState {
+    pub(crate) fn save<MoveSet: Gamestate>(
+        gamestates: &TranspositionTable<MoveSet>,
+        search: Option<(&Search, &Game)>,
+        start: &Game
+    ) -> Self {
+        WorkerState {
+            gamestates: gamestates
+                .iter()
+                .map(|(&key, bucket)| (key, bucket.iter().map(Gamestate::to_sets).collect()))
+                .collect(),
+            search: search.map(|(search, game)| search.save(game, start)),
+        }
+    }
+
+    // A root left by another thread, saved along with the worker that has taken it over.
+    pub(crate) fn pending(search: SearchState) -> Self {
+        WorkerState {
+            gamestates: Vec::new(),
+            search: Some(search),
+        }
+    }
+
+    // Adds the saved table to `gamestates`, and returns the saved search.
+    pub(crate) fn restore<MoveSet: Gamestate>(
+        self,
+        gamestates: &mut TranspositionTable<MoveSet>
+    ) -> Option<SearchState> {
+        for (key, bucket) in self.gamestates {
+            gamestates
+                .entry(key)
+                .or_default()
+                .extend(bucket.into_iter().map(MoveSet::from_sets));
+        }
+        self.search
+    }
+}
+
+// A `Search`, along with the position it had reached.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub(crate) struct SearchState {
+    // Sets played after the start.
+    best: Vec<Set>,
+    history: Vec<(Duration, usize)>,
+    outcomes: Vec<RootOutcome>,
+    skipped_roots: usize,
+    nodes: usize,
+    lookups: usize,
+    hits: usize,
+    // The root being searched, how long it has been searched for, the positions played before it
+    // and the highest score reached from it.
+    root: Option<(Point, Duration, usize, usize)>,
+    // Sets played after the start to reach the position the search is at.
+    path: Vec<Set>,
+    siblings: Vec<(usize, VecDeque<(Set, Point)>)>,
+}
+
+impl SearchState {
+    // Fails unless the best game and the path replay from `start`, and every level of siblings
+    // but the deepest is at a move of the path, as `Search::restore` and `resume_root` expect.
+    fn check(&self, start: &Game) -> Result<(), String> {
+        start
+            .replay(self.best.iter().cloned())
+            .map_err(|error| format!("saved best game can't be replayed: {}", error))?;
+        start
+            .replay(self.path.iter().cloned())
+            .map_err(|error| format!("saved path can't be replayed: {}", error))?;
+        let above = self.siblings.len().saturating_sub(1);
+        let moves = start.score()..start.score() + self.path.len();
+        if self.siblings[..above].iter().any(|(played, _)| !moves.contains(played)) {
+            return Err("saved moves to try don't follow the saved path".to_owned());
+        }
+        Ok(())
+    }
+}
+
+impl Search {
+    pub(crate) fn save(&self, game: &Game, start: &Game) -> SearchState {
+        SearchState {
+            best: self.best.sets()[start.score()..].to_vec(),
+            history: self.history.clone(),
+            outcomes: self.outcomes.clone(),
+            skipped_roots: self.skipped_roots,
+            nodes: self.nodes,
+            lookups: self.lookups,
+            hits: self.hits,
+            root: self
+                .root
+                .map(|(root, started, nodes)| (root, started.elapsed(), nodes, self.root_score)),
+            path: game.sets()[start.score()..].to_vec(),
+            siblings: self.siblings.clone(),
+        }
+    }
+
+    // Takes on the results and the position of a saved search, returning the sets to play after
+    // the start to get back to that position.
+    pub(crate) fn restore(&mut self, state: SearchState, start: &Game) -> Vec<Set> {
+        self.best = start.replay(state.best).expect("Saved best game can't be replayed.");
+        self.history = state.history;
+        self.outcomes = state.outcomes;
+        self.skipped_roots = state.skipped_roots;
+        self.nodes = state.nodes;
+        self.lookups = state.lookups;
+        self.hits = state.hits;
+        self.reported = (self.nodes, self.lookups, self.hits);
+        if let Some((root, time, nodes, score)) = state.root {
+            let started = Instant::now().checked_sub(time).unwrap_or_else(Instant::now);
+            self.root = Some((root, started, nodes));
+            self.root_score = score;
+        }
+        self.siblings = state.siblings;
+        state.path
+    }
+}
+
+// Lets the workers of a search stop together at regular intervals, so that their state can be
+// saved all at once.
+#[derive(Debug)]
+pub(crate) struct Pause {
+    interval: Duration,
+    requested: AtomicBool,
+    // When the next checkpoint is due, and how many have been saved.
+    due: Mutex<(Instant, usize)>,
+    released: Condvar,
+}
+
+impl Pause {
+    pub(crate) fn new(interval: Duration) -> Self {
+        Pause {
+            interval,
+            requested: AtomicBool::new(false),
+            due: Mutex::new((Instant::now() + interval, 0)),
+            released: Condvar::new(),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn requested(&self) -> bool {
+        self.requested.load(Ordering::Relaxed)
+    }
+
+    // Asks every worker to stop if a checkpoint is due.
+    pub(crate) fn poll(&self) {
+        let due = self.due.lock().expect("Failed to get lock on checkpoint time.");
+        if Instant::now() >= due.0 {
+            self.requested.store(true, Ordering::Relaxed);
+        }
+    }
+
+    // Checkpoints saved so far. Workers read this before they tell anyone they've suspended
+    // themselves, since the checkpoint can be saved before they get round to waiting for it.
+    pub(crate) fn saved(&self) -> usize {
+        self.due.lock().expect("Failed to get lock on checkpoint time.").1
+    }
+
+    // Blocks until a checkpoint after the first `saved` has been saved.
+    pub(crate) fn wait(&self, saved: usize) {
+        let mut due = self.due.lock().expect("Failed to get lock on checkpoint time.");
+        while due.1 == saved {
+            due = self.released.wait(due).expect("Failed to get lock on checkpoint time.");
+        }
+    }
+
+    // Lets the workers carry on after a checkpoint, and schedules the next one.
+    pub(crate) fn release(&self) {
+        let mut due = self.due.lock().expect("Failed to get lock on checkpoint time.");
+        *due = (Instant::now() + self.interval, due.1 + 1);
+        self.requested.store(false, Ordering::Relaxed);
+        self.released.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::set::Direction;
+    use std::env::temp_dir;
+    use std::fs::remove_file;
+    use std::process;
+
+    // Saves `checkpoint` and loads it back.
+    fn reload(checkpoint: &Checkpoint, name: &str) -> io::Result<Checkpoint> {
+        let path = temp_dir().join(format!("dots-{}-{}.checkpoint", name, process::id()));
+        checkpoint.save(&path).expect("Failed to save checkpoint.");
+        let loaded = Checkpoint::load(&path);
+        remove_file(&path).expect("Failed to remove checkpoint.");
+        loaded
+    }
+
+    #[test]
+    fn load_rejects_what_resuming_cant_replay() {
+        let start = Game::new();
+        let checkpoint = Checkpoint::new::<AHashSet<Set>>(&start);
+        assert_eq!(reload(&checkpoint, "valid").unwrap().start().sets(), start.sets());
+        // Sets of one point, which `Rules::new` would have refused.
+        let mut short = Checkpoint::new::<AHashSet<Set>>(&start);
+        short.rules.line_length = 1;
+        let error = reload(&short, "short").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        // Nothing to play a set on this far from the cross.
+        let nowhere = Set::new(Point::new(100, 100), Direction::H, 0, 5);
+        let mut start_moved = Checkpoint::new::<AHashSet<Set>>(&start);
+        start_moved.start.push(nowhere);
+        let error = reload(&start_moved, "start").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        let mut best_moved = Checkpoint::new::<AHashSet<Set>>(&start);
+        best_moved.finished.best.push(nowhere);
+        let error = reload(&best_moved, "best").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        let mut path_moved = Checkpoint::new::<AHashSet<Set>>(&start);
+        let search = SearchState { path: vec![nowhere], ..SearchState::default() };
+        path_moved.workers.push(WorkerState::pending(search));
+        let error = reload(&path_moved, "path").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
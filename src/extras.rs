@@ -3,7 +3,7 @@ use std::iter::Iterator;
 
 const DIRECTIONS: [Direction; 4] = [Direction::V, Direction::SP, Direction::H, Direction::SN];
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Default)]
 pub struct DirectionIter {
     d: u8,
 }
@@ -0,0 +1,25 @@
+use crate::game::Game;
+use crate::methods::Search;
+
+// Checks shared by the tests of the solvers that play games out from the start to the end.
+
+// Runs `solve` from the cross twice, which has to find the same game the same way both times.
+pub(crate) fn assert_repeatable(solve: impl Fn(Game) -> Search) {
+    let first = solve(Game::new());
+    let second = solve(Game::new());
+    assert_eq!(first.best().sets(), second.best().sets());
+    assert_eq!(first.nodes(), second.nodes());
+    let scores = |search: &Search| {
+        search.history().iter().map(|&(_, score)| score).collect::<Vec<_>>()
+    };
+    assert_eq!(scores(&first), scores(&second));
+}
+
+// Runs `solve` from the cross, whose best game has to be a finished game that replays legally.
+pub(crate) fn assert_best_replays(solve: impl Fn(Game) -> Search) {
+    let start = Game::new();
+    let search = solve(start.clone());
+    let game = start.replay(search.best().sets().iter().cloned()).unwrap();
+    assert_eq!(game.score(), search.best().score());
+    assert!(game.legal_moves().is_empty());
+}
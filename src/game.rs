@@ -1,170 +1,536 @@
+use crate::board::{Board, Storage};
 use crate::extras::DirectionIter;
 use crate::point::Point;
+use crate::rules::Rules;
 use crate::set::Set;
-use crate::DESIRED_SCORE;
-use ahash::{AHashMap, AHashSet};
-
-pub const STARTING_POINTS: [Point; 36] = [
-    Point { x: 0, y: 4 },
-    Point { x: -1, y: 4 },
-    Point { x: -1, y: 3 },
-    Point { x: -1, y: 2 },
-    Point { x: -1, y: 1 },
-    Point { x: -2, y: 1 },
-    Point { x: -3, y: 1 },
-    Point { x: -4, y: 1 },
-    Point { x: -4, y: 0 },
-    Point { x: -4, y: -1 },
-    Point { x: -4, y: -2 },
-    Point { x: -3, y: -2 },
-    Point { x: -2, y: -2 },
-    Point { x: -1, y: -2 },
-    Point { x: -1, y: -3 },
-    Point { x: -1, y: -4 },
-    Point { x: -1, y: -5 },
-    Point { x: 0, y: -5 },
-    Point { x: 1, y: -5 },
-    Point { x: 2, y: -5 },
-    Point { x: 2, y: -4 },
-    Point { x: 2, y: -3 },
-    Point { x: 2, y: -2 },
-    Point { x: 3, y: -2 },
-    Point { x: 4, y: -2 },
-    Point { x: 5, y: -2 },
-    Point { x: 5, y: -1 },
-    Point { x: 5, y: 0 },
-    Point { x: 5, y: 1 },
-    Point { x: 4, y: 1 },
-    Point { x: 3, y: 1 },
-    Point { x: 2, y: 1 },
-    Point { x: 2, y: 2 },
-    Point { x: 2, y: 3 },
-    Point { x: 2, y: 4 },
-    Point { x: 1, y: 4 },
-];
+use crate::symmetry::{symmetries_of, Symmetry};
+use ahash::AHashMap;
+use std::fs::read_to_string;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+use std::sync::Arc;
+
+// Default starting position for sets of 5 points: the standard 36-point cross. Other lengths get a
+// cross scaled to match, see `Game::with_rules`.
+pub const CROSS_THICKNESS: i16 = 3;
+pub const CROSS_ARM: i16 = 3;
+
+// Dots along the outline of a Greek cross whose centre square is `thickness` units across, with
+// arms as wide as the centre square sticking out `arm` units past it. Points are walked
+// anticlockwise, starting directly above the origin.
+pub fn greek_cross(thickness: i16, arm: i16) -> Vec<Point> {
+    assert!(thickness >= 1 && arm >= 0, "A cross needs a positive thickness and arm length.");
+    let (left, right) = (-(thickness / 2), thickness - thickness / 2);
+    let (top, bottom) = (thickness / 2, thickness / 2 - thickness);
+    let corners = [
+        (left, top + arm),
+        (left, top),
+        (left - arm, top),
+        (left - arm, bottom),
+        (left, bottom),
+        (left, bottom - arm),
+        (right, bottom - arm),
+        (right, bottom),
+        (right + arm, bottom),
+        (right + arm, top),
+        (right, top),
+        (right, top + arm),
+    ];
+    let mut points = Vec::with_capacity(4 * thickness as usize + 8 * arm as usize);
+    for (i, &(x, y)) in corners.iter().enumerate() {
+        let (end_x, end_y) = corners[(i + 1) % corners.len()];
+        let end = Point::new(end_x, end_y);
+        let step = ((end_x - x).signum(), (end_y - y).signum());
+        let mut point = Point::new(x, y);
+        while point != end {
+            points.push(point);
+            point.step(step);
+        }
+    }
+    let first = points
+        .iter()
+        .position(|point| point.x == 0 && point.y == top + arm)
+        .unwrap();
+    points.rotate_left(first);
+    points
+}
 
 #[derive(Clone, Debug)]
 pub struct Game {
-    pub(crate) points: AHashMap<Point, u8>,
+    pub(crate) points: Board,
     pub(crate) sets: Vec<Set>,
+    // Xor of the Zobrist keys of `sets` mapped by each of `symmetries`, so positions with the same
+    // sets played in any order hash the same. The first is the hash of the sets as they are.
+    pub(crate) zobrists: [u64; 8],
+    pub(crate) rules: Rules,
+    // Every set that can be added right now, with the point it would add. `add_set` and
+    // `remove_set` only recheck the sets through the points of the set they add or remove, since
+    // no other set's legality can change.
+    pub(crate) moves: AHashMap<Set, Point>,
+    // The dots the game was seeded with, in the order they were given. Solvers use these as their
+    // root candidates.
+    pub(crate) starting_points: Arc<[Point]>,
+    pub(crate) symmetries: Arc<[Symmetry]>,
 }
 
 impl Game {
     pub fn new() -> Self {
-        let mut points = AHashMap::with_capacity(STARTING_POINTS.len() + DESIRED_SCORE);
-        for &point in STARTING_POINTS.iter() {
-            points.insert(point, 0);
+        Game::with_rules(Rules::default())
+    }
+
+    // Starts from a cross with thickness and arms of `line_length - 2`, which is the standard cross
+    // for 5 points. A cross of the same size for every length leaves 6T without any moves and
+    // lets 4T games go on for hundreds of moves.
+    pub fn with_rules(rules: Rules) -> Self {
+        let size = (rules.line_length as i16 - 2).max(1);
+        Game::from_points(greek_cross(size, size), rules)
+    }
+
+    pub fn from_points<I: IntoIterator<Item = Point>>(starting_points: I, rules: Rules) -> Self {
+        let starting_points = starting_points.into_iter().collect::<Vec<_>>();
+        let mut points = Board::new(Storage::default());
+        let mut ordered = Vec::with_capacity(starting_points.len());
+        for point in starting_points {
+            if points.insert(point, 0).is_none() {
+                ordered.push(point);
+            }
         }
-        Game {
+        let mut game = Game {
             points,
-            sets: Vec::with_capacity(DESIRED_SCORE),
+            sets: Vec::new(),
+            zobrists: [0; 8],
+            rules,
+            moves: AHashMap::new(),
+            symmetries: symmetries_of(&ordered).into(),
+            starting_points: ordered.into(),
+        };
+        game.moves = game.scan_moves();
+        game
+    }
+
+    // The same game with its points stored as `storage`.
+    pub fn with_storage(mut self, storage: Storage) -> Self {
+        if storage != self.points.storage() {
+            let mut points = Board::new(storage);
+            for (point, flags) in self.points.iter() {
+                points.insert(point, flags);
+            }
+            self.points = points;
+        }
+        self
+    }
+
+    pub fn storage(&self) -> Storage {
+        self.points.storage()
+    }
+
+    // Loads a starting configuration from a plain text file with one point per line, written as
+    // `x,y`, `(x,y)` or `x y`. Blank lines and anything after a `#` are ignored.
+    pub fn from_file<P: AsRef<Path>>(path: P, rules: Rules) -> io::Result<Self> {
+        let text = read_to_string(path)?;
+        Ok(Game::from_points(parse_points(&text)?, rules))
+    }
+
+    pub fn rules(&self) -> Rules {
+        self.rules
+    }
+
+    pub fn starting_points(&self) -> &[Point] {
+        &self.starting_points
+    }
+
+    pub fn sets(&self) -> &[Set] {
+        &self.sets
+    }
+
+    pub fn points(&self) -> impl Iterator<Item = Point> + '_ {
+        self.points.iter().map(|(point, _)| point)
+    }
+
+    #[inline]
+    pub fn zobrist(&self) -> u64 {
+        self.zobrists[0]
+    }
+
+    #[inline]
+    fn toggle_zobrists(&mut self, set: Set) {
+        for (zobrist, &symmetry) in self.zobrists.iter_mut().zip(self.symmetries.iter()) {
+            *zobrist ^= set.transform(symmetry).zobrist();
         }
     }
 
     pub fn add_set(&mut self, set: Set, point: Point) {
         self.sets.push(set);
+        self.toggle_zobrists(set);
         self.points.insert(point, 0);
-        let masks = [
-            set.direction.set_out_t_mask(),
-            set.direction.set_inout_t_mask(),
-            set.direction.set_inout_t_mask(),
-            set.direction.set_inout_t_mask(),
-            set.direction.set_in_t_mask(),
-        ];
-        let mut set_point = set.start_point();
-        let step = set.direction.single_step();
-        for i in 0..5 {
+        let last = set.length as usize - 1;
+        for (i, set_point) in set.points().enumerate() {
+            let mask = if i == 0 {
+                set.direction.set_out_t_mask()
+            } else if i == last {
+                set.direction.set_in_t_mask()
+            } else {
+                set.direction.set_inout_t_mask()
+            };
             let flags = self.points.get_mut(&set_point).unwrap();
-            *flags = *flags | masks[i];
-            set_point.step(step);
+            *flags |= mask;
         }
+        self.update_moves(set);
     }
 
     pub fn add_set_nomod_flags(&mut self, set: Set) {
         self.sets.push(set);
+        self.toggle_zobrists(set);
     }
 
     pub fn remove_set(&mut self, set: Set, point: Point) {
         self.sets.pop();
+        self.toggle_zobrists(set);
         self.points.remove(&point);
-        let masks = [
-            set.direction.set_out_f_mask(),
-            set.direction.set_inout_f_mask(),
-            set.direction.set_inout_f_mask(),
-            set.direction.set_inout_f_mask(),
-            set.direction.set_in_f_mask(),
-        ];
-        let mut set_point = set.start_point();
-        let step = set.direction.single_step();
-        for i in 0..5 {
+        let last = set.length as usize - 1;
+        for (i, set_point) in set.points().enumerate() {
             if point != set_point {
+                let mask = if i == 0 {
+                    set.direction.set_out_f_mask()
+                } else if i == last {
+                    set.direction.set_in_f_mask()
+                } else {
+                    set.direction.set_inout_f_mask()
+                };
                 let flags = self.points.get_mut(&set_point).unwrap();
-                *flags = *flags & masks[i];
+                *flags &= mask;
+            }
+        }
+        self.update_moves(set);
+    }
+
+    // Rechecks every set through a point of `set` after it's been added or removed.
+    fn update_moves(&mut self, set: Set) {
+        let length = self.rules.line_length;
+        for set_point in set.points() {
+            for direction in DirectionIter::new() {
+                for offset in 0..length as i16 {
+                    let candidate = Set::new(set_point, direction, offset, length);
+                    match self.valid_add_set(candidate) {
+                        Some(point) => self.moves.insert(candidate, point),
+                        None => self.moves.remove(&candidate),
+                    };
+                }
             }
-            set_point.step(step);
         }
     }
 
     pub fn remove_set_nomod_flags(&mut self) {
-        self.sets.pop();
+        if let Some(set) = self.sets.pop() {
+            self.toggle_zobrists(set);
+        }
     }
 
+    // The point `test` adds if it's legal. See `check_add_set` for why it isn't.
+    #[inline]
     pub fn valid_add_set(&self, test: Set) -> Option<Point> {
-        let mut new = None;
-        let mut point = test.start_point();
-        let step = test.direction.single_step();
-        let masks = [
-            test.direction.get_out_mask(),
-            test.direction.get_inout_mask(),
-            test.direction.get_inout_mask(),
-            test.direction.get_inout_mask(),
-            test.direction.get_in_mask(),
-        ];
-        for &mask in masks.iter() {
-            if let Some(&flags) = self.points.get(&point) {
-                if flags & mask > 0 {
-                    return None;
-                }
-            } else if new.is_none() {
-                new = Some(point);
-            } else {
-                return None;
-            }
-            point.step(step);
-        }
-        new
+        self.check_add_set(test).ok()
     }
 
+    #[inline]
     pub fn possible_moves(&self) -> usize {
-        let mut moves: AHashSet<Set> = AHashSet::with_capacity(DESIRED_SCORE);
-        for (&point, &flags) in self.points.iter() {
+        self.moves.len()
+    }
+
+    // Every set that can be added along with the point it adds. Moves are sorted so that their
+    // order doesn't depend on the hash map's, which keeps seeded searches reproducible.
+    pub fn legal_moves(&self) -> Vec<(Set, Point)> {
+        let mut moves = self.moves.iter().map(|(&set, &point)| (set, point)).collect::<Vec<_>>();
+        moves.sort_unstable_by_key(|(set, _)| set.packed());
+        moves
+    }
+
+    // Finds every legal move by trying every set through every point.
+    fn scan_moves(&self) -> AHashMap<Set, Point> {
+        let mut moves = AHashMap::new();
+        for (point, flags) in self.points.iter() {
             // Point has a set in all directions
             if flags == 255 {
                 continue;
             }
             for direction in DirectionIter::new() {
-                let (offset_lb, offset_ub) =
-                    if (flags & direction.get_inout_mask()).count_ones() == 2 {
-                        continue;
-                    } else if flags & direction.get_in_mask() > 0 {
-                        (0, 1)
-                    } else if flags & direction.get_out_mask() > 0 {
-                        (4, 5)
-                    } else {
-                        (0, 5)
-                    };
+                let (offset_lb, offset_ub) = match self.rules.offsets(flags, direction) {
+                    Some(offsets) => offsets,
+                    None => continue,
+                };
                 for offset in offset_lb..offset_ub {
-                    let set = Set::new(point, direction, offset);
-                    if self.valid_add_set(set).is_some() {
-                        moves.insert(set);
+                    let set = Set::new(point, direction, offset, self.rules.line_length);
+                    if let Some(new) = self.valid_add_set(set) {
+                        moves.insert(set, new);
                     }
                 }
             }
         }
-        moves.len()
+        moves
     }
 
     pub fn score(&self) -> usize {
         self.sets.len()
     }
 }
+
+impl Default for Game {
+    fn default() -> Self {
+        Game::new()
+    }
+}
+
+fn parse_points(text: &str) -> io::Result<Vec<Point>> {
+    let mut points = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let invalid = || io::Error::new(
+            ErrorKind::InvalidData,
+            format!("line {}: expected a point like `x,y`, got `{}`", i + 1, line)
+        );
+        let line = line.trim_start_matches('(').trim_end_matches(')');
+        let mut coords = line
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|coord| !coord.is_empty())
+            .map(|coord| coord.parse::<i16>());
+        match (coords.next(), coords.next(), coords.next()) {
+            (Some(Ok(x)), Some(Ok(y)), None) => points.push(Point::new(x, y)),
+            _ => return Err(invalid()),
+        }
+    }
+    Ok(points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Variant;
+    use crate::set::Direction;
+    use rand::{Rng, SeedableRng};
+    use rand_pcg::Pcg64Mcg;
+    use std::env::temp_dir;
+    use std::fs::{remove_file, write};
+    use std::iter;
+    use std::process;
+
+    const STANDARD_CROSS: [Point; 36] = [
+        Point { x: 0, y: 4 },
+        Point { x: -1, y: 4 },
+        Point { x: -1, y: 3 },
+        Point { x: -1, y: 2 },
+        Point { x: -1, y: 1 },
+        Point { x: -2, y: 1 },
+        Point { x: -3, y: 1 },
+        Point { x: -4, y: 1 },
+        Point { x: -4, y: 0 },
+        Point { x: -4, y: -1 },
+        Point { x: -4, y: -2 },
+        Point { x: -3, y: -2 },
+        Point { x: -2, y: -2 },
+        Point { x: -1, y: -2 },
+        Point { x: -1, y: -3 },
+        Point { x: -1, y: -4 },
+        Point { x: -1, y: -5 },
+        Point { x: 0, y: -5 },
+        Point { x: 1, y: -5 },
+        Point { x: 2, y: -5 },
+        Point { x: 2, y: -4 },
+        Point { x: 2, y: -3 },
+        Point { x: 2, y: -2 },
+        Point { x: 3, y: -2 },
+        Point { x: 4, y: -2 },
+        Point { x: 5, y: -2 },
+        Point { x: 5, y: -1 },
+        Point { x: 5, y: 0 },
+        Point { x: 5, y: 1 },
+        Point { x: 4, y: 1 },
+        Point { x: 3, y: 1 },
+        Point { x: 2, y: 1 },
+        Point { x: 2, y: 2 },
+        Point { x: 2, y: 3 },
+        Point { x: 2, y: 4 },
+        Point { x: 1, y: 4 },
+    ];
+
+    #[test]
+    fn default_cross_is_standard_cross() {
+        assert_eq!(greek_cross(CROSS_THICKNESS, CROSS_ARM), STANDARD_CROSS.to_vec());
+        assert_eq!(Game::new().starting_points(), &STANDARD_CROSS[..]);
+    }
+
+    #[test]
+    fn cross_sizes() {
+        for thickness in 1..6 {
+            for arm in 0..6 {
+                let cross = greek_cross(thickness, arm);
+                let game = Game::from_points(cross.iter().cloned(), Rules::default());
+                assert_eq!(cross.len(), (4 * thickness + 8 * arm) as usize);
+                assert_eq!(game.starting_points().len(), cross.len());
+            }
+        }
+    }
+
+    #[test]
+    fn parallel_sets_share_an_endpoint_only_when_touching() {
+        // Nine points in a row with the third and seventh missing, so that the sets over the
+        // first five and the last five points each add one point and share the fifth.
+        let row = (0..9).filter(|&x| x != 2 && x != 6).map(|x| Point::new(x, 0));
+        let first = Set::new(Point::new(0, 0), Direction::H, 0, 5);
+        let second = Set::new(Point::new(4, 0), Direction::H, 0, 5);
+        for &(variant, legal) in [(Variant::Touching, true), (Variant::Disjoint, false)].iter() {
+            let mut game = Game::from_points(row.clone(), Rules::new(variant, 5));
+            let point = game.valid_add_set(first).expect("First set should be legal.");
+            game.add_set(first, point);
+            assert_eq!(game.valid_add_set(second).is_some(), legal, "{:?}", variant);
+        }
+    }
+
+    #[test]
+    fn default_cross_fits_line_length() {
+        let variants = [
+            Rules::new(Variant::Touching, 4),
+            Rules::new(Variant::Disjoint, 4),
+            Rules::new(Variant::Touching, 6),
+        ];
+        for &rules in variants.iter() {
+            let mut game = Game::with_rules(rules);
+            assert!(!game.legal_moves().is_empty(), "{:?}", rules);
+            while let Some(&(set, point)) = game.legal_moves().first() {
+                game.add_set(set, point);
+                assert!(game.score() < 200, "{:?} game doesn't end", rules);
+            }
+        }
+    }
+
+    #[test]
+    fn symmetric_positions_share_canonical_form() {
+        let start = Game::new();
+        assert_eq!(start.symmetries().len(), 8);
+        // Some image has to be a different raw position for the canonical form to be doing work.
+        let mut moved = false;
+        for &(first, point) in start.legal_moves().iter() {
+            let mut after_first = start.clone();
+            after_first.add_set(first, point);
+            for &(second, point) in after_first.legal_moves().iter() {
+                let mut game = after_first.clone();
+                game.add_set(second, point);
+                let canonical = game.canonical();
+                for &symmetry in start.symmetries().iter() {
+                    let moves = [first.transform(symmetry), second.transform(symmetry)];
+                    let image = start.replay(moves.iter().cloned()).unwrap();
+                    assert_eq!(image.canonical_zobrist(), game.canonical_zobrist());
+                    assert_eq!(image.canonical(), canonical, "{:?} under {:?}", moves, symmetry);
+                    moved |= image.zobrist() != game.zobrist();
+                }
+            }
+        }
+        assert!(moved);
+    }
+
+    // Plays up to `length` moves from `start` on both storages at once, picking each move with
+    // `pick` and every so often taking a few back. After every step the moves kept up to date by
+    // `add_set` and `remove_set` have to be the ones a full scan finds, and the storages have to
+    // agree on them.
+    fn play_checking_moves(
+        start: Game,
+        length: usize,
+        rng: &mut Pcg64Mcg,
+        pick: fn(&[(Set, Point)], &mut Pcg64Mcg) -> usize
+    ) -> Game {
+        let mut grid = start.clone().with_storage(Storage::Grid);
+        let mut map = start.with_storage(Storage::HashMap);
+        let mut played = Vec::new();
+        loop {
+            assert_eq!(grid.moves, grid.scan_moves(), "{:?}", grid.sets());
+            assert_eq!(map.moves, map.scan_moves(), "{:?}", map.sets());
+            let moves = grid.legal_moves();
+            assert_eq!(moves, map.legal_moves(), "{:?}", grid.sets());
+            if moves.is_empty() || played.len() >= length {
+                return grid;
+            }
+            if !played.is_empty() && rng.gen_range(0, 4) == 0 {
+                for _ in 0..rng.gen_range(1, 4).min(played.len()) {
+                    let (set, point) = played.pop().unwrap();
+                    grid.remove_set(set, point);
+                    map.remove_set(set, point);
+                }
+            } else {
+                let (set, point) = moves[pick(&moves, rng)];
+                grid.add_set(set, point);
+                map.add_set(set, point);
+                played.push((set, point));
+            }
+        }
+    }
+
+    #[test]
+    fn incremental_moves_match_full_scan() {
+        let mut rng = Pcg64Mcg::seed_from_u64(0);
+        let variants = [
+            Rules::new(Variant::Touching, 5),
+            Rules::new(Variant::Disjoint, 5),
+            Rules::new(Variant::Touching, 4),
+            Rules::new(Variant::Disjoint, 4),
+        ];
+        let random = |moves: &[(Set, Point)], rng: &mut Pcg64Mcg| rng.gen_range(0, moves.len());
+        for &rules in variants.iter() {
+            for _ in 0..10 {
+                play_checking_moves(Game::with_rules(rules), usize::MAX, &mut rng, random);
+            }
+        }
+        // Sets of two points can always extend a game to the right, so always picking the
+        // rightmost move takes it well past the window the grid started with.
+        let rules = Rules::new(Variant::Touching, 2);
+        let start = Game::from_points(iter::once(Point::new(0, 0)), rules);
+        let rightmost = |moves: &[(Set, Point)], _: &mut Pcg64Mcg| {
+            (0..moves.len()).max_by_key(|&i| moves[i].1.x).unwrap()
+        };
+        let game = play_checking_moves(start, 40, &mut rng, rightmost);
+        assert!(game.points().any(|point| point.x > 20));
+    }
+
+    #[test]
+    fn parse_points_line_forms() {
+        let text = "1,2\n(3,4)\n5 6\n  -7 , -8  \n( 9 10 )\n";
+        let points = parse_points(text).unwrap();
+        let expected = [(1, 2), (3, 4), (5, 6), (-7, -8), (9, 10)];
+        assert_eq!(points, expected.iter().map(|&(x, y)| Point::new(x, y)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn parse_points_skips_comments_and_blank_lines() {
+        let text = "# a cross\n\n1,2 # first\n   \n#3,4\n5,6\n";
+        assert_eq!(parse_points(text).unwrap(), vec![Point::new(1, 2), Point::new(5, 6)]);
+    }
+
+    #[test]
+    fn parse_points_reports_malformed_line() {
+        for &bad in ["1", "1,2,3", "a,b", "1;2", "1,"].iter() {
+            let text = format!("0,0\n# comment\n{}\n", bad);
+            let error = parse_points(&text).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::InvalidData, "{}", bad);
+            assert!(error.to_string().starts_with("line 3:"), "{}: {}", bad, error);
+        }
+    }
+
+    #[test]
+    fn loaded_file_matches_from_points() {
+        let path = temp_dir().join(format!("dots-start-{}.txt", process::id()));
+        let text = STANDARD_CROSS
+            .iter()
+            .map(|point| format!("{},{}\n", point.x, point.y))
+            .collect::<String>();
+        write(&path, text).expect("Failed to write start file.");
+        let loaded = Game::from_file(&path, Rules::default());
+        remove_file(&path).expect("Failed to remove start file.");
+        let loaded = loaded.expect("Failed to load start file.");
+        let game = Game::from_points(STANDARD_CROSS.iter().cloned(), Rules::default());
+        assert_eq!(loaded.starting_points(), game.starting_points());
+        assert_eq!(loaded.points().collect::<Vec<_>>(), game.points().collect::<Vec<_>>());
+        assert_eq!(loaded.legal_moves(), game.legal_moves());
+        assert_eq!(loaded.zobrist(), game.zobrist());
+    }
+}
@@ -0,0 +1,37 @@
+pub mod beam;
+mod board;
+mod budget;
+mod build_svg;
+mod checkpoint;
+mod extras;
+#[cfg(test)]
+mod fixtures;
+mod game;
+mod pentasol;
+pub mod mcts;
+pub mod methods;
+pub mod nmcs;
+pub mod nrpa;
+mod point;
+mod progress;
+mod replay;
+mod rules;
+mod set;
+pub mod solvers;
+mod stealing;
+mod symmetry;
+
+pub use board::Storage;
+pub use budget::Termination;
+pub use build_svg::display_game_as_svg;
+pub use checkpoint::{Checkpoint, Checkpointing, Gamestate};
+pub use extras::DirectionIter;
+pub use game::{greek_cross, Game, CROSS_ARM, CROSS_THICKNESS};
+pub use methods::{Cancel, Method, RootOutcome, Search};
+pub use point::Point;
+pub use progress::{Progress, REPORT_INTERVAL};
+pub use replay::{MoveError, ReplayError};
+pub use rules::{Rules, Variant};
+pub use set::{Direction, Set, SetIter};
+pub use solvers::SolverConfig;
+pub use symmetry::{Dihedral, Symmetry, DIHEDRALS};
@@ -1,21 +1,24 @@
-mod build_svg;
-mod extras;
-mod game;
-mod methods;
-mod point;
-mod set;
-mod solvers;
-
-use build_svg::display_game_as_svg;
-use game::{Game, STARTING_POINTS};
-use methods::{
-    base,
-    base_highest_set,
-    base_highest_set_vec,
+use clap::{App, Arg, ArgMatches};
+use dots::{
+    display_game_as_svg,
+    Checkpoint,
+    Checkpointing,
+    Game,
+    Gamestate,
+    Point,
+    Progress,
+    Rules,
+    Set,
+    Variant
+};
+use dots::beam::{beam, BeamConfig};
+use dots::mcts::{mcts, MctsConfig, Playout};
+use dots::methods::{
     cmp_hpm,
     cmp_mnx_hpm,
     cmp_quad_hpm,
     Method,
+    Search,
     spm_intoiter,
     spm_intoiter_rev,
     store_hs,
@@ -24,21 +27,438 @@ use methods::{
     v_eq,
     v_hs_eq
 };
-use solvers::multithreaded_method;
+use dots::nmcs::nmcs;
+use dots::nrpa::{nrpa, NrpaConfig};
+use dots::solvers::{
+    multithreaded_method,
+    multithreaded_rayon_method,
+    resume_multithreaded_method,
+    resume_singlethreaded_method,
+    singlethreaded_method,
+    SolverConfig,
+    work_stealing_method
+};
+use std::cmp::Ordering;
+use std::fs::{read_to_string, write};
+use std::process::exit;
+use std::str::FromStr;
+use std::sync::mpsc::channel;
+use std::thread::spawn;
+use std::time::Duration;
 
-pub const DESIRED_SCORE: usize = 60;
+const DESIRED_SCORE: &str = "60";
+
+type MoveInfo = (usize, Set, Point);
 
 fn main() {
-    let method = Method::new(v_hs_eq, store_hs, store_npm_s_p, cmp_hpm, spm_intoiter_rev);
-    let try_best = multithreaded_method(method);
-    if let Some(best) = try_best {
-        println!("Got: {}", best.score());
-        for set in best.sets.iter() {
-            println!("{}", set);
+    let matches = App::new("dots")
+        .about("Searches for long games of Morpion solitaire.")
+        .arg(Arg::with_name("solver")
+            .long("solver")
+            .takes_value(true)
+            .possible_values(&[
+                "threads", "rayon", "steal", "single", "nmcs", "nrpa", "beam", "mcts"
+            ])
+            .default_value("threads")
+            .help("Solver backend: depth-first search on threads, rayon, work stealing threads or \
+                a single thread, or Nested Monte Carlo Search, Nested Rollout Policy Adaptation, \
+                beam search or Monte Carlo Tree Search"))
+        .arg(Arg::with_name("order")
+            .long("order")
+            .takes_value(true)
+            .possible_values(&["hpm", "mnx_hpm", "quad_hpm"])
+            .default_value("hpm")
+            .help("Move ordering (`cmp_hpm`, `cmp_mnx_hpm` or `cmp_quad_hpm`)"))
+        .arg(Arg::with_name("store")
+            .long("store")
+            .takes_value(true)
+            .possible_values(&["hs", "vec"])
+            .default_value("hs")
+            .help("Gamestate dedupe store: hash sets or vecs"))
+        .arg(Arg::with_name("target")
+            .long("target")
+            .takes_value(true)
+            .default_value(DESIRED_SCORE)
+            .help("Score to stop at"))
+        .arg(Arg::with_name("maximise")
+            .long("maximise")
+            .help("Search for the highest score instead of stopping at the target"))
+        .arg(Arg::with_name("progress")
+            .long("progress")
+            .help("Report nodes, speed, deepest depth, best score and dedupe hits of the \
+                depth-first solvers to stderr every second"))
+        .arg(Arg::with_name("time")
+            .long("time")
+            .takes_value(true)
+            .help("Stop after this many seconds and keep the best game so far"))
+        .arg(Arg::with_name("nodes")
+            .long("nodes")
+            .takes_value(true)
+            .help("Stop after playing this many positions, counting every move of the playouts \
+                and expansions of nmcs, nrpa, beam and mcts, and keep the best game so far"))
+        .arg(Arg::with_name("checkpoint")
+            .long("checkpoint")
+            .takes_value(true)
+            .help("Save the state of the threads or single solver to this file every so often"))
+        .arg(Arg::with_name("every")
+            .long("every")
+            .takes_value(true)
+            .default_value("600")
+            .help("Seconds between two checkpoints"))
+        .arg(Arg::with_name("resume")
+            .long("resume")
+            .takes_value(true)
+            .help("Carry on with the search saved in this checkpoint, with the threads or single \
+                solver and the same --store and --order as before"))
+        .arg(Arg::with_name("threads")
+            .long("threads")
+            .takes_value(true)
+            .help("Worker threads for the threads, rayon, steal and mcts solvers \
+                [default: one per CPU]"))
+        .arg(Arg::with_name("level")
+            .long("level")
+            .takes_value(true)
+            .help("Nesting level for nmcs and nrpa [default: 1 for nmcs, 3 for nrpa]"))
+        .arg(Arg::with_name("seed")
+            .long("seed")
+            .takes_value(true)
+            .default_value("0")
+            .help("Random seed for nmcs, nrpa and mcts"))
+        .arg(Arg::with_name("iterations")
+            .long("iterations")
+            .takes_value(true)
+            .help("Iterations per level for nrpa or per tree for mcts \
+                [default: 100 for nrpa, 10000 for mcts]"))
+        .arg(Arg::with_name("rate")
+            .long("rate")
+            .takes_value(true)
+            .default_value("1.0")
+            .help("Learning rate for nrpa"))
+        .arg(Arg::with_name("exploration")
+            .long("exploration")
+            .takes_value(true)
+            .help("Weight of exploration in UCB for mcts [default: 0.2]"))
+        .arg(Arg::with_name("playout")
+            .long("playout")
+            .takes_value(true)
+            .possible_values(&["random", "hpm"])
+            .default_value("random")
+            .help("Playouts for mcts: random, or the move leaving the most possible moves"))
+        .arg(Arg::with_name("width")
+            .long("width")
+            .takes_value(true)
+            .default_value("100")
+            .help("Positions kept at each depth for beam"))
+        .arg(Arg::with_name("variant")
+            .long("variant")
+            .takes_value(true)
+            .possible_values(&["T", "D"])
+            .default_value("T")
+            .help("Touching (T) or disjoint (D) rules"))
+        .arg(Arg::with_name("length")
+            .long("length")
+            .takes_value(true)
+            .default_value("5")
+            .help("Number of points in a set"))
+        .arg(Arg::with_name("start")
+            .long("start")
+            .takes_value(true)
+            .help("File of starting points, one `x,y` per line \
+                [default: a cross sized for --length]"))
+        .arg(Arg::with_name("output")
+            .long("output")
+            .short("o")
+            .takes_value(true)
+            .default_value("game-{score}.svg")
+            .help("Where to write the best game; `{score}` is replaced by its score"))
+        .arg(Arg::with_name("format")
+            .long("format")
+            .takes_value(true)
+            .possible_values(&["svg", "text", "pentasol"])
+            .help("Output format [default: svg for .svg outputs, text otherwise]"))
+        .arg(Arg::with_name("verify")
+            .long("verify")
+            .takes_value(true)
+            .help("Check that a Pentasol record is a legal game from the start and exit"))
+        .get_matches();
+    let variant = match matches.value_of("variant") {
+        Some("D") => Variant::Disjoint,
+        _ => Variant::Touching,
+    };
+    let length = parse_arg(&matches, "length");
+    if length < Rules::MIN_LINE_LENGTH {
+        eprintln!(
+            "Invalid value for --length: sets need at least {} points",
+            Rules::MIN_LINE_LENGTH
+        );
+        exit(1);
+    }
+    let rules = Rules::new(variant, length);
+    let start = match matches.value_of("start") {
+        Some(path) => Game::from_file(path, rules).unwrap_or_else(|e| {
+            eprintln!("Failed to load {}: {}", path, e);
+            exit(1);
+        }),
+        None => Game::with_rules(rules),
+    };
+    if let Some(path) = matches.value_of("verify") {
+        let result = read_to_string(path).and_then(|text| Game::from_pentasol(&text, start));
+        match result {
+            Ok(game) => println!("{}: legal game of {} moves", path, game.score()),
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                exit(1);
+            },
         }
-        let filename = format!("game-{}.svg", best.score());
-        display_game_as_svg(&filename, &best);
-    } else {
+        return;
+    }
+    let threads = matches.value_of("threads").map(|_| parse_arg(&matches, "threads"));
+    if threads == Some(0) {
+        eprintln!("Invalid value for --threads: the solvers need at least one thread");
+        exit(1);
+    }
+    let config = SolverConfig {
+        target: if matches.is_present("maximise") {
+            None
+        } else {
+            Some(parse_arg(&matches, "target"))
+        },
+        threads,
+        cancel: None,
+        checkpoint: matches.value_of("checkpoint").map(|path| Checkpointing {
+            path: path.into(),
+            interval: Duration::from_secs(parse_arg(&matches, "every")),
+        }),
+        time_limit: matches.value_of("time").map(|value| {
+            Duration::try_from_secs_f64(parse_arg(&matches, "time")).unwrap_or_else(|_| {
+                eprintln!("Invalid value for --time: {}", value);
+                exit(1);
+            })
+        }),
+        node_limit: matches.value_of("nodes").map(|_| parse_arg(&matches, "nodes")),
+        progress: if matches.is_present("progress") {
+            let (send, recv) = channel();
+            spawn(move || {
+                for progress in recv {
+                    print_progress(progress);
+                }
+            });
+            Some(send)
+        } else {
+            None
+        },
+    };
+    let target = config.target;
+    let solver = matches.value_of("solver").unwrap();
+    if (matches.is_present("checkpoint") || matches.is_present("resume"))
+        && solver != "threads"
+        && solver != "single"
+    {
+        eprintln!("Only the threads and single solvers can save and resume checkpoints");
+        exit(1);
+    }
+    let resume = matches.value_of("resume").map(|path| {
+        Checkpoint::load(path).unwrap_or_else(|e| {
+            eprintln!("Failed to load {}: {}", path, e);
+            exit(1);
+        })
+    });
+    let search = match solver {
+        "nmcs" => {
+            let level = parse_arg_or(&matches, "level", 1);
+            nmcs(start, level, parse_arg(&matches, "seed"), config)
+        },
+        "nrpa" => {
+            let nrpa_config = NrpaConfig {
+                level: parse_arg_or(&matches, "level", NrpaConfig::default().level),
+                iterations: parse_arg_or(&matches, "iterations", NrpaConfig::default().iterations),
+                learning_rate: parse_arg(&matches, "rate"),
+                seed: parse_arg(&matches, "seed"),
+            };
+            nrpa(start, nrpa_config, config)
+        },
+        "mcts" => {
+            let default = MctsConfig::default();
+            let mcts_config = MctsConfig {
+                iterations: parse_arg_or(&matches, "iterations", default.iterations),
+                exploration: parse_arg_or(&matches, "exploration", default.exploration),
+                playout: match matches.value_of("playout") {
+                    Some("hpm") => Playout::Heuristic(Game::possible_moves),
+                    _ => Playout::Random,
+                },
+                seed: parse_arg(&matches, "seed"),
+                ..default
+            };
+            mcts(start, mcts_config, config)
+        },
+        "beam" => {
+            let width = parse_arg(&matches, "width");
+            if width == 0 {
+                eprintln!("Invalid value for --width: the beam has to keep at least one position");
+                exit(1);
+            }
+            let beam_config = BeamConfig { width, ..BeamConfig::default() };
+            let (search, depths) = beam(start, beam_config, config);
+            for depth in depths {
+                println!(
+                    "depth {}: kept {} of {} ({} duplicates), evaluations {}..{}",
+                    depth.depth,
+                    depth.kept,
+                    depth.candidates,
+                    depth.duplicates,
+                    depth.worst,
+                    depth.best
+                );
+            }
+            search
+        },
+        solver => dfs(&matches, solver, start, resume, config),
+    };
+    if search.roots() > 0 {
+        for outcome in search.outcomes() {
+            println!(
+                "Root {}: reached {}, {} nodes in {:.3}s",
+                outcome.root,
+                outcome.score,
+                outcome.nodes,
+                outcome.time.as_secs_f64()
+            );
+        }
+        println!(
+            "Searched {} roots, skipped {} symmetric to those, {} nodes in total",
+            search.roots(),
+            search.skipped_roots(),
+            search.nodes()
+        );
+        println!("Dedupe hits: {} of {} lookups", search.hits(), search.lookups());
+    }
+    for &(time, score) in search.history() {
+        println!("[{:.3}s] {}", time.as_secs_f64(), score);
+    }
+    let best = search.best();
+    // Only a missed target is worth a sad face. Maximising has no target to miss.
+    if target.is_some() && !search.reached(best) {
         println!(":c");
     }
+    println!("Stopped: {}", search.termination());
+    if let Some(error) = search.checkpoint_error() {
+        eprintln!("Failed to save checkpoint: {}", error);
+    }
+    println!("Got: {}", best.score());
+    for set in best.sets() {
+        println!("{}", set);
+    }
+    let filename = matches
+        .value_of("output")
+        .unwrap()
+        .replace("{score}", &best.score().to_string());
+    let format = matches
+        .value_of("format")
+        .unwrap_or(if filename.ends_with(".svg") { "svg" } else { "text" });
+    match format {
+        "svg" => display_game_as_svg(&filename, best),
+        "pentasol" => {
+            write(&filename, best.to_pentasol()).expect("Failed to save Pentasol output.");
+        },
+        _ => {
+            let text = best.sets().iter().map(|set| format!("{}\n", set)).collect::<String>();
+            write(&filename, text).expect("Failed to save text output.");
+        },
+    }
+}
+
+fn print_progress(progress: Progress) {
+    eprintln!(
+        "[{:.0}s] {} nodes, {:.0}/s, deepest {}, best {}, {:.1}% dedupe hits",
+        progress.elapsed.as_secs_f64(),
+        progress.nodes,
+        progress.nodes_per_second,
+        progress.deepest,
+        progress.best,
+        100.0 * progress.hit_rate()
+    );
+}
+
+// Depth-first search with the `Method` picked by --store and --order, or carrying on with the one
+// saved in `resume`.
+fn dfs(
+    matches: &ArgMatches,
+    solver: &str,
+    start: Game,
+    resume: Option<Checkpoint>,
+    config: SolverConfig
+) -> Search {
+    let order = matches.value_of("order").unwrap();
+    // Orderings by quadrant try the fewest possible moves first, the others the most.
+    match (matches.value_of("store").unwrap(), order) {
+        ("vec", "quad_hpm") => {
+            let method = Method::new(v_eq, store_v, store_npm_s_p, cmp_quad_hpm, spm_intoiter);
+            solve(solver, method, start, resume, config)
+        },
+        ("vec", _) => {
+            let method = Method::new(v_eq, store_v, store_npm_s_p, cmp(order), spm_intoiter_rev);
+            solve(solver, method, start, resume, config)
+        },
+        (_, "quad_hpm") => {
+            let method = Method::new(v_hs_eq, store_hs, store_npm_s_p, cmp_quad_hpm, spm_intoiter);
+            solve(solver, method, start, resume, config)
+        },
+        _ => {
+            let method =
+                Method::new(v_hs_eq, store_hs, store_npm_s_p, cmp(order), spm_intoiter_rev);
+            solve(solver, method, start, resume, config)
+        },
+    }
+}
+
+fn solve<MoveSet, MoveIter>(
+    solver: &str,
+    method: Method<MoveSet, MoveInfo, MoveIter>,
+    start: Game,
+    resume: Option<Checkpoint>,
+    config: SolverConfig
+) -> Search
+    where
+        MoveSet: 'static + Clone + Send + Gamestate,
+        MoveIter: 'static + Iterator<Item = (Set, Point)>
+{
+    if let Some(checkpoint) = resume {
+        let search = match solver {
+            "single" => resume_singlethreaded_method(method, checkpoint, config),
+            _ => resume_multithreaded_method(method, checkpoint, config),
+        };
+        return search.unwrap_or_else(|e| {
+            eprintln!("Failed to resume: {}", e);
+            exit(1);
+        });
+    }
+    match solver {
+        "rayon" => multithreaded_rayon_method(method, start, config),
+        "single" => singlethreaded_method(method, start, config),
+        "steal" => work_stealing_method(method, start, config),
+        _ => multithreaded_method(method, start, config),
+    }
+}
+
+fn cmp(order: &str) -> fn(&MoveInfo, &MoveInfo) -> Ordering {
+    match order {
+        "mnx_hpm" => cmp_mnx_hpm,
+        _ => cmp_hpm,
+    }
+}
+
+fn parse_arg<T: FromStr>(matches: &ArgMatches, name: &str) -> T {
+    let value = matches.value_of(name).unwrap();
+    value.parse().unwrap_or_else(|_| {
+        eprintln!("Invalid value for --{}: {}", name, value);
+        exit(1);
+    })
+}
+
+fn parse_arg_or<T: FromStr>(matches: &ArgMatches, name: &str, default: T) -> T {
+    if matches.is_present(name) {
+        parse_arg(matches, name)
+    } else {
+        default
+    }
 }
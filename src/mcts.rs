@@ -0,0 +1,240 @@
+use ahash::AHashMap;
+use crate::beam::Evaluation;
+use crate::game::Game;
+use crate::methods::Search;
+use crate::point::Point;
+use crate::set::Set;
+use crate::solvers::SolverConfig;
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg64Mcg;
+use rayon::prelude::*;
+use std::time::Instant;
+
+#[derive(Copy, Clone, Debug)]
+pub enum Playout {
+    // Uniformly random moves.
+    Random,
+    // The move whose position evaluates highest, with ties broken at random.
+    Heuristic(Evaluation),
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct MctsConfig {
+    // Iterations run on each tree.
+    pub iterations: usize,
+    // Weight of the exploration term of UCB.
+    pub exploration: f64,
+    // How much the best score below a position counts for against the mean score, from 0 to 1.
+    // Single-player searches only care about the best line, so it's worth leaning on.
+    pub max_weight: f64,
+    pub playout: Playout,
+    pub seed: u64,
+}
+
+impl Default for MctsConfig {
+    fn default() -> Self {
+        MctsConfig {
+            iterations: 10000,
+            exploration: 0.2,
+            max_weight: 0.5,
+            playout: Playout::Random,
+            seed: 0,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+struct Node {
+    visits: u32,
+    total: f64,
+    best: usize,
+}
+
+// Visits and scores of every position in the tree, keyed by the Zobrist hash of the position
+// rather than by path so that transpositions share their statistics.
+type Table = AHashMap<u64, Node>;
+
+// UCT with one tree per thread of the pool from `SolverConfig::thread_pool`, each seeded
+// differently. A single `Game` is walked up and down each tree with `add_set` and `remove_set`.
+pub fn mcts(start: Game, mcts: MctsConfig, config: SolverConfig) -> Search {
+    let template = Search::new(&start, &config, Instant::now());
+    let pool = config.thread_pool();
+    pool.install(|| {
+        (0..pool.current_num_threads())
+            .into_par_iter()
+            .map(|tree| {
+                let mut search = template.split(&start);
+                let mut rng = Pcg64Mcg::seed_from_u64(mcts.seed.wrapping_add(tree as u64));
+                let mut game = start.clone();
+                let mut table = Table::default();
+                table.insert(game.zobrist(), Node::default());
+                for _ in 0..mcts.iterations {
+                    if search.stopped() {
+                        break;
+                    }
+                    iterate(&mut game, &mut table, mcts, &mut rng, &mut search);
+                }
+                search
+            })
+            .reduce_with(|mut search, other| {
+                search.merge(other);
+                search
+            })
+            .unwrap_or_else(|| template.split(&start))
+            .finish()
+    })
+}
+
+// One selection, expansion, playout and backpropagation, leaving `game` as it found it.
+fn iterate(
+    game: &mut Game,
+    table: &mut Table,
+    mcts: MctsConfig,
+    rng: &mut Pcg64Mcg,
+    search: &mut Search
+) {
+    let mut path = vec![game.zobrist()];
+    let mut played = Vec::new();
+    // Selection, stopping at the first move into a position that isn't in the table yet.
+    loop {
+        let moves = game.legal_moves();
+        if moves.is_empty() {
+            break;
+        }
+        let key = game.zobrist();
+        let unvisited = moves
+            .iter()
+            .filter(|&&(set, _)| !table.contains_key(&(key ^ set.zobrist())))
+            .cloned()
+            .collect::<Vec<_>>();
+        let (set, point) = if !unvisited.is_empty() {
+            unvisited[rng.gen_range(0, unvisited.len())]
+        } else {
+            select(table, key, &moves, mcts, search.best().score())
+        };
+        game.add_set(set, point);
+        search.count_node();
+        played.push((set, point));
+        path.push(game.zobrist());
+        if !unvisited.is_empty() {
+            table.insert(game.zobrist(), Node::default());
+            break;
+        }
+    }
+    playout(game, &mut played, mcts.playout, rng, search);
+    search.record(game);
+    let score = game.score();
+    for key in path {
+        let node = table.get_mut(&key).unwrap();
+        node.visits += 1;
+        node.total += score as f64;
+        node.best = node.best.max(score);
+    }
+    for &(set, point) in played.iter().rev() {
+        game.remove_set(set, point);
+    }
+}
+
+// The child maximising UCB, with the mean score mixed with the best one and both scaled by the
+// best score found so far so they stay within 0 to 1.
+fn select(
+    table: &Table,
+    key: u64,
+    moves: &[(Set, Point)],
+    mcts: MctsConfig,
+    best: usize
+) -> (Set, Point) {
+    let parent_visits = (table[&key].visits.max(1) as f64).ln();
+    let scale = best.max(1) as f64;
+    let ucb = |&(set, _): &(Set, Point)| {
+        let node = table[&(key ^ set.zobrist())];
+        let visits = node.visits.max(1) as f64;
+        let mean = node.total / visits / scale;
+        let max = node.best as f64 / scale;
+        (1.0 - mcts.max_weight) * mean
+            + mcts.max_weight * max
+            + mcts.exploration * (parent_visits / visits).sqrt()
+    };
+    moves
+        .iter()
+        .cloned()
+        .max_by(|a, b| ucb(a).partial_cmp(&ucb(b)).unwrap())
+        .unwrap()
+}
+
+fn playout(
+    game: &mut Game,
+    played: &mut Vec<(Set, Point)>,
+    playout: Playout,
+    rng: &mut Pcg64Mcg,
+    search: &mut Search
+) {
+    loop {
+        let moves = game.legal_moves();
+        if moves.is_empty() {
+            break;
+        }
+        let (set, point) = match playout {
+            Playout::Random => moves[rng.gen_range(0, moves.len())],
+            Playout::Heuristic(evaluation) => {
+                let mut best = Vec::new();
+                let mut best_evaluation = 0;
+                for &(set, point) in moves.iter() {
+                    game.add_set(set, point);
+                    let value = evaluation(game);
+                    game.remove_set(set, point);
+                    if best.is_empty() || value > best_evaluation {
+                        best.clear();
+                        best_evaluation = value;
+                    }
+                    if value == best_evaluation {
+                        best.push((set, point));
+                    }
+                }
+                best[rng.gen_range(0, best.len())]
+            },
+        };
+        game.add_set(set, point);
+        search.count_node();
+        played.push((set, point));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{assert_best_replays, assert_repeatable};
+
+    fn run(start: Game, seed: u64) -> Search {
+        let mcts_config = MctsConfig { iterations: 200, seed, ..MctsConfig::default() };
+        mcts(start, mcts_config, SolverConfig { threads: Some(1), ..SolverConfig::default() })
+    }
+
+    #[test]
+    fn iterate_leaves_game_unchanged() {
+        let mcts = MctsConfig::default();
+        let start = Game::new();
+        let mut game = start.clone();
+        let mut table = Table::default();
+        table.insert(game.zobrist(), Node::default());
+        let mut rng = Pcg64Mcg::seed_from_u64(0);
+        let mut search = Search::new(&start, &SolverConfig::default(), Instant::now());
+        for _ in 0..50 {
+            iterate(&mut game, &mut table, mcts, &mut rng, &mut search);
+            assert_eq!(game.zobrist(), start.zobrist());
+            assert_eq!(game.sets(), start.sets());
+            assert_eq!(game.legal_moves(), start.legal_moves());
+        }
+        assert_eq!(table[&start.zobrist()].visits, 50);
+    }
+
+    #[test]
+    fn same_seed_same_game_on_one_thread() {
+        assert_repeatable(|start| run(start, 3));
+    }
+
+    #[test]
+    fn best_game_replays() {
+        assert_best_replays(|start| run(start, 0));
+    }
+}
@@ -1,21 +1,30 @@
-use ahash::AHashSet;
-use crate::DESIRED_SCORE;
-use crate::extras::DirectionIter;
+use ahash::{AHashMap, AHashSet};
 use crate::game::Game;
+use crate::budget::{Budget, Termination};
+use crate::checkpoint::Pause;
 use crate::point::Point;
+use crate::progress::Tally;
 use crate::set::Set;
+use crate::solvers::SolverConfig;
+use crate::stealing::Shared;
+use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
+use std::collections::VecDeque;
 use std::hash::Hash;
-use std::iter::{Iterator, Map, Rev};
-use std::vec::IntoIter;
+use std::iter::Iterator;
+use std::sync::Arc;
+use std::sync::atomic::{self, AtomicBool};
+use std::time::{Duration, Instant};
 
+// Gamestates are handed to `set_comparison` and `store_new_set` as the `&Vec<Set>` they've always
+// taken, which is why the functions of that shape below allow `clippy::ptr_arg`.
 pub struct Method<MoveSet, MoveInfo, MoveIter>
     where
         MoveInfo: Hash + Eq + PartialEq,
         MoveIter: Iterator<Item = (Set, Point)>
 {
     set_comparison: fn(&MoveSet, &Vec<Set>) -> bool,
-    store_new_set: fn(&mut Vec<Vec<MoveSet>>, &Vec<Set>),
+    store_new_set: fn(&mut Vec<MoveSet>, &Vec<Set>),
     store_move_info: fn(&mut AHashSet<MoveInfo>, usize, Set, Point),
     sort_move_info: fn(&MoveInfo, &MoveInfo) -> Ordering,
     move_iter: fn(Vec<MoveInfo>) -> MoveIter
@@ -26,7 +35,7 @@ impl<MoveSet, MoveInfo: Hash + Eq + PartialEq, MoveIter> Method<MoveSet, MoveInf
 {
     pub fn new(
         set_comparison: fn(&MoveSet, &Vec<Set>) -> bool,
-        store_new_set: fn(&mut Vec<Vec<MoveSet>>, &Vec<Set>),
+        store_new_set: fn(&mut Vec<MoveSet>, &Vec<Set>),
         store_move_info: fn(&mut AHashSet<MoveInfo>, usize, Set, Point),
         sort_move_info: fn(&MoveInfo, &MoveInfo) -> Ordering,
         move_iter: fn(Vec<MoveInfo>) -> MoveIter
@@ -57,12 +66,329 @@ impl<MoveSet, MoveInfo, MoveIter> Copy for Method<MoveSet, MoveInfo, MoveIter>
         MoveIter: Iterator<Item = (Set, Point)>
 {}
 
-pub fn base<MoveSet, MoveInfo: Hash + Eq + PartialEq, MoveIter: Iterator<Item = (Set, Point)>>(
-    gamestates: &mut Vec<Vec<MoveSet>>,
+// Positions already searched, keyed by `Game::canonical_zobrist` so that a position and its images
+// under the start's symmetries share a key. Each bucket holds the canonical form of every stored
+// position with that key in the `Method`'s own format, and its `set_comparison` only has to tell
+// apart the positions of one bucket, so checking for a duplicate takes constant time however many
+// positions have been stored.
+pub type TranspositionTable<MoveSet> = AHashMap<u64, Vec<MoveSet>>;
+
+// Where a depth-first search looks up and stores the positions it has seen: a worker's own
+// `TranspositionTable`, or one shared by every worker of a work stealing search.
+pub trait Transpositions<MoveSet> {
+    // Runs `f` on the bucket of positions stored under `key`.
+    fn bucket<R>(&mut self, key: u64, f: impl FnOnce(&mut Vec<MoveSet>) -> R) -> R;
+}
+
+impl<MoveSet> Transpositions<MoveSet> for TranspositionTable<MoveSet> {
+    #[inline]
+    fn bucket<R>(&mut self, key: u64, f: impl FnOnce(&mut Vec<MoveSet>) -> R) -> R {
+        f(self.entry(key).or_default())
+    }
+}
+
+// Asks a running search to stop. Clones share the same flag, so a clone kept by the caller can stop
+// a search that's running on other threads.
+#[derive(Clone, Debug, Default)]
+pub struct Cancel(Arc<AtomicBool>);
+
+impl Cancel {
+    pub fn new() -> Self {
+        Cancel::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, atomic::Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(atomic::Ordering::Relaxed)
+    }
+}
+
+// How the search of one root went: the highest score reached from it, the positions played on the
+// way, and how long it took.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct RootOutcome {
+    pub root: Point,
+    pub score: usize,
+    pub nodes: usize,
+    pub time: Duration,
+}
+
+// Everything a search carries between nodes besides the game itself: when to stop, and the best
+// game seen anywhere in the tree so far along with when each improvement was found.
+#[derive(Clone, Debug)]
+pub struct Search {
+    pub(crate) target: Option<usize>,
+    // Cancelled as soon as any part of the search reaches the target, so that the searches of
+    // every other part stop too.
+    pub(crate) found: Cancel,
+    // The caller's `SolverConfig::cancel`.
+    pub(crate) cancel: Option<Cancel>,
+    pub(crate) best: Game,
+    pub(crate) history: Vec<(Duration, usize)>,
+    pub(crate) started: Instant,
+    // Every root point searched, and the number of root points left out because a symmetry of
+    // the start maps them onto a root that was searched.
+    pub(crate) outcomes: Vec<RootOutcome>,
+    pub(crate) skipped_roots: usize,
+    // Positions played.
+    pub(crate) nodes: usize,
+    // The root being searched, when it was started and the positions played before it, and the
+    // highest score reached from it so far.
+    pub(crate) root: Option<(Point, Instant, usize)>,
+    pub(crate) root_score: usize,
+    // Positions looked up in the transposition table, and how many of them were already there.
+    pub(crate) lookups: usize,
+    pub(crate) hits: usize,
+    // Where to report progress to, and the counts as of the last report.
+    pub(crate) progress: Option<Arc<Tally>>,
+    pub(crate) reported: (usize, usize, usize),
+    // Set when searching with other workers that can take over untried moves.
+    pub(crate) stealing: Option<Arc<Shared>>,
+    // Moves not tried yet at each level of the current path, with the number of sets played
+    // before them.
+    pub(crate) siblings: Vec<(usize, VecDeque<(Set, Point)>)>,
+    // Set when the solver saves checkpoints, and once this search has stopped for one or been
+    // cut short.
+    pub(crate) pause: Option<Arc<Pause>>,
+    pub(crate) suspended: bool,
+    // Why the latest checkpoint that failed to save did, if one has. The search carries on
+    // without it, leaving the previous checkpoint as it was.
+    pub(crate) checkpoint_error: Option<String>,
+    // The caller's time and node limits, if there are any.
+    pub(crate) budget: Option<Arc<Budget>>,
+    // Set when the solver returns.
+    pub(crate) termination: Termination,
+}
+
+impl Search {
+    pub fn new(start: &Game, config: &SolverConfig, started: Instant) -> Self {
+        Search {
+            target: config.target,
+            found: Cancel::new(),
+            cancel: config.cancel.clone(),
+            best: start.clone(),
+            history: Vec::new(),
+            started,
+            outcomes: Vec::new(),
+            skipped_roots: 0,
+            nodes: 0,
+            root: None,
+            root_score: 0,
+            lookups: 0,
+            hits: 0,
+            progress: config.progress.clone().map(|send| Arc::new(Tally::new(send, started))),
+            reported: (0, 0, 0),
+            stealing: None,
+            siblings: Vec::new(),
+            pause: None,
+            suspended: false,
+            checkpoint_error: None,
+            budget: Budget::new(config).map(Arc::new),
+            termination: Termination::default(),
+        }
+    }
+
+    // A search of another part of the same tree, from `start`, that stops when this one does.
+    pub(crate) fn split(&self, start: &Game) -> Self {
+        Search {
+            target: self.target,
+            found: self.found.clone(),
+            cancel: self.cancel.clone(),
+            best: start.clone(),
+            history: Vec::new(),
+            started: self.started,
+            outcomes: Vec::new(),
+            skipped_roots: 0,
+            nodes: 0,
+            root: None,
+            root_score: 0,
+            lookups: 0,
+            hits: 0,
+            progress: self.progress.clone(),
+            reported: (0, 0, 0),
+            stealing: None,
+            siblings: Vec::new(),
+            pause: self.pause.clone(),
+            suspended: false,
+            checkpoint_error: None,
+            budget: self.budget.clone(),
+            termination: Termination::default(),
+        }
+    }
+
+    #[inline]
+    pub fn reached(&self, game: &Game) -> bool {
+        self.target.is_some_and(|target| game.score() >= target)
+    }
+
+    // Whether the search should give up: some part of it has reached the target, the caller
+    // cancelled it, or it has run out of time or nodes.
+    #[inline]
+    pub fn stopped(&self) -> bool {
+        self.found.is_cancelled()
+            || self.cancel.as_ref().is_some_and(Cancel::is_cancelled)
+            || self.budget.as_ref().is_some_and(|budget| budget.exhausted())
+    }
+
+    // Whether the caller or the budget has stopped a search that saves checkpoints, short of the
+    // target. It then stops the way it does for a checkpoint, so that where it got to is saved.
+    pub(crate) fn cut_short(&self) -> bool {
+        self.pause.is_some() && !self.found.is_cancelled() && self.stopped()
+    }
+
+    pub fn best(&self) -> &Game {
+        &self.best
+    }
+
+    // (time since the search started, new best score) for every improvement, in order.
+    pub fn history(&self) -> &[(Duration, usize)] {
+        &self.history
+    }
+
+    pub fn roots(&self) -> usize {
+        self.outcomes.len()
+    }
+
+    pub fn outcomes(&self) -> &[RootOutcome] {
+        &self.outcomes
+    }
+
+    pub fn nodes(&self) -> usize {
+        self.nodes
+    }
+
+    pub fn lookups(&self) -> usize {
+        self.lookups
+    }
+
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    pub fn skipped_roots(&self) -> usize {
+        self.skipped_roots
+    }
+
+    // Why the latest checkpoint that failed to save did, if any has failed.
+    pub fn checkpoint_error(&self) -> Option<&str> {
+        self.checkpoint_error.as_deref()
+    }
+
+    // Why the solver returned this search.
+    pub fn termination(&self) -> Termination {
+        self.termination
+    }
+
+    #[inline]
+    pub(crate) fn record(&mut self, game: &Game) {
+        self.root_score = self.root_score.max(game.score());
+        if game.score() > self.best.score() {
+            self.best = game.clone();
+            self.history.push((self.started.elapsed(), game.score()));
+            if self.reached(game) {
+                self.found.cancel();
+            }
+        }
+    }
+
+    // Whether the search has been asked to stop for a checkpoint. Once it has, `suspended` is set
+    // and every level of the search returns, leaving the game and `siblings` as they are, so that
+    // it can be saved and then picked up again with `resume_root`.
+    #[inline]
+    pub(crate) fn suspending(&mut self) -> bool {
+        if self.pause.as_ref().is_some_and(|pause| pause.requested()) {
+            self.suspended = true;
+        }
+        self.suspended
+    }
+
+    pub(crate) fn finish_root(&mut self) {
+        if let Some((root, started, nodes)) = self.root.take() {
+            self.outcomes.push(RootOutcome {
+                root,
+                score: self.root_score,
+                nodes: self.nodes - nodes,
+                time: started.elapsed(),
+            });
+        }
+    }
+
+    // Counts a position played, towards `SolverConfig::node_limit` too.
+    #[inline]
+    pub(crate) fn count_node(&mut self) {
+        self.nodes += 1;
+        if let Some(budget) = &self.budget {
+            budget.spend();
+        }
+    }
+
+    // Adds the counts since the last call to the progress reports, from a position at `depth`.
+    pub(crate) fn report(&mut self, depth: usize) {
+        if let Some(tally) = &self.progress {
+            let (nodes, lookups, hits) = self.reported;
+            let best = self.best.score();
+            tally.add(self.nodes - nodes, self.lookups - lookups, self.hits - hits, best, depth);
+            self.reported = (self.nodes, self.lookups, self.hits);
+        }
+    }
+
+    // Stops reporting progress, and works out why the search ended. The solvers return their
+    // search finished, so that whoever reads the reports sees them end once the solver has
+    // returned.
+    pub(crate) fn finish(mut self) -> Self {
+        self.progress = None;
+        let exceeded = self.budget.as_ref().and_then(|budget| budget.exceeded());
+        self.termination = if self.reached(&self.best) {
+            Termination::Reached
+        } else if let Some(limit) = exceeded {
+            limit
+        } else if self.cancel.as_ref().is_some_and(Cancel::is_cancelled) {
+            Termination::Cancelled
+        } else {
+            Termination::Exhausted
+        };
+        self
+    }
+
+    // Folds the results of a search of another part of the tree into this one. Both histories are
+    // interleaved by time, keeping only entries that improved on everything found before them.
+    pub fn merge(&mut self, other: Search) {
+        if other.best.score() > self.best.score() {
+            self.best = other.best;
+        }
+        self.outcomes.extend(other.outcomes);
+        self.skipped_roots += other.skipped_roots;
+        self.nodes += other.nodes;
+        self.lookups += other.lookups;
+        self.hits += other.hits;
+        self.history.extend(other.history);
+        self.history.sort_by_key(|&(time, _)| time);
+        let mut best = 0;
+        self.history.retain(|&(_, score)| if score > best {
+            best = score;
+            true
+        } else {
+            false
+        });
+    }
+}
+
+pub fn base<MoveSet, MoveInfo, MoveIter, Table>(
+    gamestates: &mut Table,
     mut game: Game,
-    point: Point,
-    method: Method<MoveSet, MoveInfo, MoveIter>
-) -> Game {
+    root: Point,
+    method: Method<MoveSet, MoveInfo, MoveIter>,
+    search: &mut Search
+) -> Game
+    where
+        MoveInfo: Hash + Eq + PartialEq,
+        MoveIter: Iterator<Item = (Set, Point)>,
+        Table: Transpositions<MoveSet>
+{
     let Method {
         set_comparison,
         store_new_set,
@@ -70,44 +396,22 @@ pub fn base<MoveSet, MoveInfo: Hash + Eq + PartialEq, MoveIter: Iterator<Item =
         sort_move_info,
         move_iter
     } = method;
-    let mut possible_moves: AHashSet<MoveInfo> = AHashSet::with_capacity(DESIRED_SCORE);
-    let flags = *game.points.get(&point).unwrap();
-    for direction in DirectionIter::new() {
-        let (offset_lb, offset_ub) = if (flags & direction.get_inout_mask()).count_ones() == 2 {
-            // If this point is being used in and out in a given direction, it can no longer be the
-            // start or end point for a set. Move on to the next direction.
+    search.root = Some((root, Instant::now(), search.nodes));
+    search.root_score = game.score();
+    let mut possible_moves: AHashSet<MoveInfo> = AHashSet::new();
+    for (set, point) in game.legal_moves() {
+        if !set.points().any(|set_point| set_point == root) {
             continue;
-        } else if flags & direction.get_in_mask() > 0 {
-            // If this point is being used coming into the point from a given direction, this point
-            // and the next four in the same direction can be part of a set.
-            (0, 1)
-        } else if flags & direction.get_out_mask() > 0 {
-            // If this point is being used going out of the point from a given direction, this point
-            // and the next four in the same direction (but stepping backwards) can be part of a
-            // set.
-            (4, 5)
+        }
+        game.add_set_nomod_flags(set);
+        if !seen_or_store(gamestates, set_comparison, store_new_set, &game, search) {
+            game.remove_set_nomod_flags();
+            game.add_set(set, point);
+            let num_possible_moves = game.possible_moves();
+            store_move_info(&mut possible_moves, num_possible_moves, set, point);
+            game.remove_set(set, point);
         } else {
-            // This point can be the start, end, or in the middle of a set.
-            (0, 5)
-        };
-        for offset in offset_lb..offset_ub {
-            let set = Set::new(point, direction, offset);
-            if let Some(point) = game.valid_add_set(set) {
-                game.add_set(set, point);
-                if !gamestates[game.score() - 1]
-                    .iter()
-                    .any(|gamestate| set_comparison(gamestate, &game.sets))
-                {
-                    store_new_set(gamestates, &game.sets);
-                    game.remove_set_nomod_flags();
-                    game.add_set(set, point);
-                    let num_possible_moves = game.possible_moves();
-                    store_move_info(&mut possible_moves, num_possible_moves, set, point);
-                    game.remove_set(set, point);
-                } else {
-                    game.remove_set_nomod_flags();
-                }
-            }
+            game.remove_set_nomod_flags();
         }
     }
     let mut sorted_possible_moves = Vec::with_capacity(possible_moves.len());
@@ -115,25 +419,73 @@ pub fn base<MoveSet, MoveInfo: Hash + Eq + PartialEq, MoveIter: Iterator<Item =
         sorted_possible_moves.push(item);
     }
     sorted_possible_moves.sort_unstable_by(sort_move_info);
-    for (set, point) in move_iter(sorted_possible_moves) {
-        game.add_set(set, point);
-        if game.score() >= DESIRED_SCORE {
-            break;
+    // Branch off into recursion-land for each possible move. We don't have to worry about filtering
+    // the moves we've collected here since that's already been done in the possible move
+    // collection loop.
+    try_moves(gamestates, &mut game, move_iter(sorted_possible_moves), method, search);
+    if !search.suspended {
+        search.finish_root();
+    }
+    game
+}
+
+// Picks up the search of a root that stopped for a checkpoint. `path` holds the sets played
+// after `start` to reach the position it stopped at, and `search.siblings` the moves it had left
+// to try at each level on the way there. Returns the game like `base` does.
+pub(crate) fn resume_root<MoveSet, MoveInfo, MoveIter, Table>(
+    gamestates: &mut Table,
+    start: &Game,
+    path: &[Set],
+    method: Method<MoveSet, MoveInfo, MoveIter>,
+    search: &mut Search
+) -> Game
+    where
+        MoveInfo: Hash + Eq + PartialEq,
+        MoveIter: Iterator<Item = (Set, Point)>,
+        Table: Transpositions<MoveSet>
+{
+    let mut game = start.clone();
+    let mut played = Vec::with_capacity(path.len());
+    for &set in path {
+        let point = game.try_add_set(set).expect("Saved path can't be replayed from the start.");
+        played.push((set, point));
+    }
+    // The deepest level is where the search stopped. Every level above it was in the middle of
+    // the move leading to the level below, which has to be undone once that level is done.
+    for level in (0..search.siblings.len()).rev() {
+        if play_level(gamestates, &mut game, level, method, search) {
+            search.finish_root();
+            return game;
         }
-        if branch::<MoveSet, MoveInfo, MoveIter>(gamestates, &mut game, method) {
-            break;
-        } else {
+        if search.suspended {
+            return game;
+        }
+        if level > 0 {
+            let (set, point) = played[search.siblings[level - 1].0 - start.score()];
             game.remove_set(set, point);
         }
     }
+    search.finish_root();
     game
 }
 
-fn branch<MoveSet, MoveInfo: Hash + Eq + PartialEq, MoveIter: Iterator<Item = (Set, Point)>>(
-    gamestates: &mut Vec<Vec<MoveSet>>,
+// Return value:
+// true => hit desired max - return with no further action
+// false => pop sets, remove set start
+// This thing is basically the same as the base function, except it takes a mutable reference to a
+// `Game` that it modifies, and returns a `bool` depending on whether it reached the target number
+// of moves. Literally identical otherwise.
+pub(crate) fn branch<MoveSet, MoveInfo, MoveIter, Table>(
+    gamestates: &mut Table,
     game: &mut Game,
-    method: Method<MoveSet, MoveInfo, MoveIter>
-) -> bool {
+    method: Method<MoveSet, MoveInfo, MoveIter>,
+    search: &mut Search
+) -> bool
+    where
+        MoveInfo: Hash + Eq + PartialEq,
+        MoveIter: Iterator<Item = (Set, Point)>,
+        Table: Transpositions<MoveSet>
+{
     let Method {
         set_comparison,
         store_new_set,
@@ -141,40 +493,17 @@ fn branch<MoveSet, MoveInfo: Hash + Eq + PartialEq, MoveIter: Iterator<Item = (S
         sort_move_info,
         move_iter
     } = method;
-    let mut possible_moves: AHashSet<MoveInfo> = AHashSet::with_capacity(DESIRED_SCORE);
-    for (point, flags) in game.points.clone().into_iter() {
-        if flags == 255 {
-            continue;
-        }
-        for direction in DirectionIter::new() {
-            let (offset_lb, offset_ub) = if(flags & direction.get_inout_mask()).count_ones() == 2 {
-                continue;
-            } else if flags & direction.get_in_mask() > 0 {
-                (0, 1)
-            } else if flags & direction.get_in_mask() > 0 {
-                (4, 5)
-            } else {
-                (0, 5)
-            };
-            for offset in offset_lb..offset_ub {
-                let set = Set::new(point, direction, offset);
-                if let Some(point) = game.valid_add_set(set) {
-                    game.add_set_nomod_flags(set);
-                    if !gamestates[game.score() - 1]
-                        .iter()
-                        .any(|gamestate| set_comparison(gamestate, &game.sets))
-                    {
-                        store_new_set(gamestates, &game.sets);
-                        game.remove_set_nomod_flags();
-                        game.add_set(set, point);
-                        let num_possible_moves = game.possible_moves();
-                        store_move_info(&mut possible_moves, num_possible_moves, set, point);
-                        game.remove_set(set, point);
-                    } else {
-                        game.remove_set_nomod_flags();
-                    }
-                }
-            }
+    let mut possible_moves: AHashSet<MoveInfo> = AHashSet::new();
+    for (set, point) in game.legal_moves() {
+        game.add_set_nomod_flags(set);
+        if !seen_or_store(gamestates, set_comparison, store_new_set, game, search) {
+            game.remove_set_nomod_flags();
+            game.add_set(set, point);
+            let num_possible_moves = game.possible_moves();
+            store_move_info(&mut possible_moves, num_possible_moves, set, point);
+            game.remove_set(set, point);
+        } else {
+            game.remove_set_nomod_flags();
         }
     }
     let mut sorted_possible_moves = Vec::with_capacity(possible_moves.len());
@@ -182,20 +511,122 @@ fn branch<MoveSet, MoveInfo: Hash + Eq + PartialEq, MoveIter: Iterator<Item = (S
         sorted_possible_moves.push(item);
     }
     sorted_possible_moves.sort_unstable_by(sort_move_info);
-    for (set, point) in move_iter(sorted_possible_moves) {
+    try_moves(gamestates, game, move_iter(sorted_possible_moves), method, search)
+}
+
+// Positions a search plays between adding its counts to the progress reports and checking whether a
+// checkpoint is due.
+const POLL_NODES: usize = 1024;
+
+// Plays each move in turn and branches from there. Returns `true`, leaving the game as it is, as
+// soon as a branch meets the required number of moves. Otherwise every move is undone again, and
+// it returns `false`, without trying the rest once the search has been stopped. Moves wait their
+// turn on `search.siblings`, where a work stealing search can hand them to other workers and a
+// checkpoint can save them.
+fn try_moves<MoveSet, MoveInfo, MoveIter, Table>(
+    gamestates: &mut Table,
+    game: &mut Game,
+    moves: MoveIter,
+    method: Method<MoveSet, MoveInfo, MoveIter>,
+    search: &mut Search
+) -> bool
+    where
+        MoveInfo: Hash + Eq + PartialEq,
+        MoveIter: Iterator<Item = (Set, Point)>,
+        Table: Transpositions<MoveSet>
+{
+    let level = search.siblings.len();
+    search.siblings.push((game.score(), moves.collect()));
+    play_level(gamestates, game, level, method, search)
+}
+
+// The loop of `try_moves`, over the moves left at `level` of `search.siblings`, which has to be the
+// last level. Drops the level once it's done with it, unless the search is suspended.
+fn play_level<MoveSet, MoveInfo, MoveIter, Table>(
+    gamestates: &mut Table,
+    game: &mut Game,
+    level: usize,
+    method: Method<MoveSet, MoveInfo, MoveIter>,
+    search: &mut Search
+) -> bool
+    where
+        MoveInfo: Hash + Eq + PartialEq,
+        MoveIter: Iterator<Item = (Set, Point)>,
+        Table: Transpositions<MoveSet>
+{
+    loop {
+        if search.cut_short() {
+            search.suspended = true;
+            return false;
+        }
+        if search.stopped() {
+            break;
+        }
+        if search.suspending() {
+            return false;
+        }
+        let (set, point) = match search.siblings[level].1.pop_front() {
+            Some(next) => next,
+            None => break,
+        };
         game.add_set(set, point);
-        if game.score() >= DESIRED_SCORE {
+        search.count_node();
+        if search.nodes % POLL_NODES == 0 {
+            search.report(game.score());
+            if let Some(pause) = &search.pause {
+                pause.poll();
+            }
+        }
+        search.record(game);
+        if search.reached(game) {
+            search.siblings.truncate(level);
             return true;
         }
-        if branch::<MoveSet, MoveInfo, MoveIter>(gamestates, game, method) {
+        if let Some(stealing) = &search.stealing {
+            stealing.donate(&mut search.siblings, &game.sets);
+        }
+        if branch(gamestates, game, method, search) {
+            search.siblings.truncate(level);
             return true;
+        } else if search.suspended {
+            return false;
         } else {
             game.remove_set(set, point);
         }
     }
+    search.siblings.truncate(level);
+    // Ran out of moves to try, and we didn't reach the requisite number, so return `false`.
     false
 }
 
+// Whether the position, or a symmetric one, has already been stored. If it hasn't, it is now. The
+// canonical form is only worked out once there's a stored position to compare it with, or it has
+// to be stored, since most buckets are empty.
+#[inline]
+fn seen_or_store<MoveSet, Table: Transpositions<MoveSet>>(
+    gamestates: &mut Table,
+    set_comparison: fn(&MoveSet, &Vec<Set>) -> bool,
+    store_new_set: fn(&mut Vec<MoveSet>, &Vec<Set>),
+    game: &Game,
+    search: &mut Search
+) -> bool {
+    search.lookups += 1;
+    let seen = gamestates.bucket(game.canonical_zobrist(), |bucket| {
+        let mut canonical = None;
+        let seen = bucket.iter().any(|gamestate| {
+            set_comparison(gamestate, canonical.get_or_insert_with(|| game.canonical()))
+        });
+        if !seen {
+            store_new_set(bucket, &canonical.unwrap_or_else(|| game.canonical()));
+        }
+        seen
+    });
+    if seen {
+        search.hits += 1;
+    }
+    seen
+}
+
 // h ighest
 // p ossible
 // m moves
@@ -210,7 +641,7 @@ pub fn cmp_hpm(v1: &(usize, Set, Point), v2: &(usize, Set, Point)) -> Ordering {
 pub fn cmp_mnx_hpm(v1: &(usize, Set, Point), v2: &(usize, Set, Point)) -> Ordering {
     match v1.2.x.cmp(&v2.2.x) {
         Ordering::Equal => v1.0.cmp(&v2.0),
-        cmp @ _ => cmp,
+        cmp => cmp,
     }
 }
 
@@ -219,20 +650,29 @@ pub fn cmp_mnx_hpm(v1: &(usize, Set, Point), v2: &(usize, Set, Point)) -> Orderi
 pub fn cmp_quad_hpm(v1: &(usize, Set, Point), v2: &(usize, Set, Point)) -> Ordering {
     match v1.1.start_point().quadrant().cmp(&v2.1.start_point().quadrant()) {
         Ordering::Equal => v1.0.cmp(&v2.0),
-        cmp @ _ => cmp,
+        cmp => cmp,
     }
 }
 
-pub fn store_hs(all_gamestates: &mut Vec<Vec<AHashSet<Set>>>, new_gamestate: &Vec<Set>) {
+#[allow(clippy::ptr_arg)]
+pub fn store_hs(bucket: &mut Vec<AHashSet<Set>>, new_gamestate: &Vec<Set>) {
     let mut store = AHashSet::with_capacity(new_gamestate.len());
     for &set in new_gamestate.iter() {
         store.insert(set);
     }
-    all_gamestates[new_gamestate.len() - 1].push(store);
+    bucket.push(store);
 }
 
-pub fn store_v(all_gamestates: &mut Vec<Vec<Vec<Set>>>, new_gamestate: &Vec<Set>) {
-    all_gamestates[new_gamestate.len() - 1].push(new_gamestate.clone());
+#[allow(clippy::ptr_arg)]
+pub fn store_v(bucket: &mut Vec<Vec<Set>>, new_gamestate: &Vec<Set>) {
+    bucket.push(new_gamestate.to_vec());
+}
+
+#[allow(clippy::ptr_arg)]
+pub fn store_v_sorted(bucket: &mut Vec<Vec<Set>>, new_gamestate: &Vec<Set>) {
+    let mut present = new_gamestate.to_vec();
+    present.sort_unstable_by_key(Set::packed);
+    bucket.push(present);
 }
 
 pub fn store_npm_s_p(
@@ -256,142 +696,7 @@ pub fn spm_intoiter_rev(sorted_possible_moves: Vec<(usize, Set, Point)>)
     sorted_possible_moves.into_iter().rev().map(|(_, set, point)| (set, point))
 }
 
-pub fn base_highest_set(
-    gamestates: &mut Vec<Vec<AHashSet<Set>>>,
-    mut game: Game,
-    point: Point
-) -> Game {
-    // Collect unique possible moves in this hashset
-    let mut possible_moves: AHashSet<(usize, Set, Point)> = AHashSet::with_capacity(DESIRED_SCORE);
-    // let mut possible_moves: AHashSet<(usize, Set, Point)> = AHashSet::with_capacity(DESIRED_SCORE);
-    let flags = *game.points.get(&point).expect("foo");
-    for direction in DirectionIter::new() {
-        let (offset_lb, offset_ub) = if (flags & direction.get_inout_mask()).count_ones() == 2 {
-            // If this point is being used in and out in a given direction, it can no longer be the
-            // start or end point for a set. Move on to the next direction.
-            continue;
-        } else if flags & direction.get_in_mask() > 0 {
-            // If this point is being used coming into the point from a given direction, this point
-            // and the next four in the same direction can be part of a set.
-            (0, 1)
-        } else if flags & direction.get_out_mask() > 0 {
-            // If this point is being used going out of the point from a given direction, this point
-            // and the next four in the same direction (but stepping backwards) can be part of a
-            // set.
-            (4, 5)
-        } else {
-            // This point can be the start, end, or in the middle of a set.
-            (0, 5)
-        };
-        for offset in offset_lb..offset_ub {
-            let set = Set::new(point, direction, offset);
-            if let Some(point) = game.valid_add_set(set) {
-                game.add_set(set, point);
-                if !gamestates[game.score() - 1]
-                    .iter()
-                    .any(|gs| v_hs_eq(gs, &game.sets))
-                {
-                    let mut new_gamestate = AHashSet::with_capacity(game.score());
-                    for &set in &game.sets {
-                        new_gamestate.insert(set);
-                    }
-                    gamestates[game.score() - 1].push(new_gamestate);
-                    let num_possible_moves = game.possible_moves();
-                    possible_moves.insert((num_possible_moves, set, point));
-                }
-                game.remove_set(set, point);
-            }
-        }
-    }
-    // Collect into a vec to sort
-    let mut sorted_possible_moves = Vec::with_capacity(possible_moves.len());
-    for m in possible_moves.into_iter() {
-        sorted_possible_moves.push(m);
-    }
-    // Sort by possible moves
-    sorted_possible_moves.sort_unstable_by(cmp_quad_hpm);
-    for (_, set, point) in sorted_possible_moves.into_iter() {
-        // Branch off into recursion-land for each possible move, and break out of the loop if one
-        // of the branches meets the required number of moves. Otherwise, if the branch returns and
-        // hasn't met the requested number of moves, undo the change made here and try again. We
-        // also don't have to worry about filtering the moves we've collected here since that's
-        // already been done in the possible move collection loop.
-        game.add_set(set, point);
-        if game.score() >= DESIRED_SCORE {
-            break;
-        }
-        if branch_highest_set(gamestates, &mut game) {
-            break;
-        } else {
-            game.remove_set(set, point);
-        }
-    }
-    game
-}
-
-// Return value:
-// true => hit desired max - return with no further action
-// false => pop sets, remove set start
-// This thing is basically the same as the base function, except it takes a mutable reference to a
-// `Game` that it modifies, and returns a `bool` depending on whether it reached the target number
-// of moves. Literally identical otherwise.
-fn branch_highest_set(gamestates: &mut Vec<Vec<AHashSet<Set>>>, game: &mut Game) -> bool {
-    let mut possible_moves: AHashSet<(usize, Set, Point)> = AHashSet::with_capacity(DESIRED_SCORE);
-    for (point, flags) in game.points.clone().into_iter() {
-        if flags == 255 {
-            continue;
-        }
-        for direction in DirectionIter::new() {
-            let (offset_lb, offset_ub) = if (flags & direction.get_inout_mask()).count_ones() == 2 {
-                continue;
-            } else if flags & direction.get_in_mask() > 0 {
-                (0, 1)
-            } else if flags & direction.get_out_mask() > 0 {
-                (4, 5)
-            } else {
-                (0, 5)
-            };
-            for offset in offset_lb..offset_ub {
-                let set = Set::new(point, direction, offset);
-                if let Some(point) = game.valid_add_set(set) {
-                    game.add_set(set, point);
-                    if !gamestates[game.score() - 1]
-                        .iter()
-                        .any(|gs| v_hs_eq(gs, &game.sets))
-                    {
-                        let mut new_gamestate = AHashSet::with_capacity(game.score());
-                        for &set in &game.sets {
-                            new_gamestate.insert(set);
-                        }
-                        gamestates[game.score() - 1].push(new_gamestate);
-                        let num_possible_moves = game.possible_moves();
-                        possible_moves.insert((num_possible_moves, set, point));
-                    }
-                    game.remove_set(set, point);
-                }
-            }
-        }
-    }
-    let mut sorted_possible_moves = Vec::with_capacity(possible_moves.len());
-    for m in possible_moves.into_iter() {
-        sorted_possible_moves.push(m);
-    }
-    sorted_possible_moves.sort_unstable_by(cmp_quad_hpm);
-    for (_, set, point) in sorted_possible_moves.into_iter() {
-        game.add_set(set, point);
-        if game.score() >= DESIRED_SCORE {
-            return true;
-        }
-        if branch_highest_set(gamestates, game) {
-            return true;
-        } else {
-            game.remove_set(set, point);
-        }
-    }
-    // Ran out of moves to try, and we didn't reach the requisite number, so return `false`.
-    false
-}
-
+#[allow(clippy::ptr_arg)]
 pub fn v_hs_eq(hs: &AHashSet<Set>, v: &Vec<Set>) -> bool {
     if v.len() == hs.len() {
         for item in v.iter() {
@@ -405,135 +710,30 @@ pub fn v_hs_eq(hs: &AHashSet<Set>, v: &Vec<Set>) -> bool {
     }
 }
 
-pub fn base_highest_set_vec(gamestates: &mut Vec<Vec<Vec<Set>>>, mut game: Game, point: Point) -> Game {
-    // Collect unique possible moves in this hashset
-    let mut possible_moves: AHashSet<(usize, Set, Point)> = AHashSet::with_capacity(DESIRED_SCORE);
-    let flags = *game.points.get(&point).expect("foo");
-    for direction in DirectionIter::new() {
-        let (offset_lb, offset_ub) = if (flags & direction.get_inout_mask()).count_ones() == 2 {
-            // If this point is being used in and out in a given direction, it can no longer be the
-            // start or end point for a set. Move on to the next direction.
-            continue;
-        } else if flags & direction.get_in_mask() > 0 {
-            // If this point is being used coming into the point from a given direction, this point
-            // and the next four in the same direction can be part of a set.
-            (0, 1)
-        } else if flags & direction.get_out_mask() > 0 {
-            // If this point is being used going out of the point from a given direction, this point
-            // and the next four in the same direction (but stepping backwards) can be part of a
-            // set.
-            (4, 5)
-        } else {
-            // This point can be the start, end, or in the middle of a set.
-            (0, 5)
-        };
-        for offset in offset_lb..offset_ub {
-            let set = Set::new(point, direction, offset);
-            if let Some(point) = game.valid_add_set(Set::new(point, direction, offset)) {
-                game.add_set(set, point);
-                let mut present = game.sets.clone();
-                present.sort_unstable_by(|s1, s2| s1.packed().cmp(&s2.packed()));
-                if !gamestates[game.score() - 1]
-                    .iter()
-                    .any(|gs| v_eq(&present, gs))
-                {
-                    // print!("c");
-                    gamestates[game.score() - 1].push(present);
-                    let num_possible_moves = game.possible_moves();
-                    possible_moves.insert((num_possible_moves, set, point));
-                }
-                game.remove_set(set, point);
-            }
-        }
-    }
-    // Collect into a vec to sort
-    let mut sorted_possible_moves = Vec::with_capacity(possible_moves.len());
-    for m in possible_moves.into_iter() {
-        sorted_possible_moves.push(m);
-    }
-    // Sort by possible moves
-    sorted_possible_moves.sort_unstable_by(cmp_quad_hpm);
-    for (_, set, point) in sorted_possible_moves.into_iter() {
-        // Branch off into recursion-land for each possible move, and break out of the loop if one
-        // of the branches meets the required number of moves. Otherwise, if the branch returns and
-        // hasn't met the requested number of moves, undo the change made here and try again. We
-        // also don't have to worry about filtering the moves we've collected here since that's
-        // already been done in the possible move collection loop.
-        game.add_set(set, point);
-        if game.score() >= DESIRED_SCORE {
-            break;
-        }
-        if branch_highest_set_vec(gamestates, &mut game) {
-            break;
-        } else {
-            game.remove_set(set, point);
-        }
-    }
-    game
+// `base` with hashset gamestates, trying moves in order of quadrant, then fewest possible moves.
+pub fn base_highest_set(
+    gamestates: &mut TranspositionTable<AHashSet<Set>>,
+    game: Game,
+    point: Point,
+    search: &mut Search
+) -> Game {
+    let method = Method::new(v_hs_eq, store_hs, store_npm_s_p, cmp_quad_hpm, spm_intoiter);
+    base(gamestates, game, point, method, search)
 }
 
-// Return value:
-// true => hit desired max - return with no further action
-// false => pop sets, remove set start
-// This thing is basically the same as the base function, except it takes a mutable reference to a
-// `Game` that it modifies, and returns a `bool` depending on whether it reached the target number
-// of moves. Literally identical otherwise.
-fn branch_highest_set_vec(gamestates: &mut Vec<Vec<Vec<Set>>>, game: &mut Game) -> bool {
-    let mut possible_moves: AHashSet<(usize, Set, Point)> = AHashSet::with_capacity(DESIRED_SCORE);
-    for (point, flags) in game.points.clone().into_iter() {
-        if flags == 255 {
-            continue;
-        }
-        for direction in DirectionIter::new() {
-            let (offset_lb, offset_ub) = if (flags & direction.get_inout_mask()).count_ones() == 2 {
-                continue;
-            } else if flags & direction.get_in_mask() > 0 {
-                (0, 1)
-            } else if flags & direction.get_out_mask() > 0 {
-                (4, 5)
-            } else {
-                (0, 5)
-            };
-            for offset in offset_lb..offset_ub {
-                let set = Set::new(point, direction, offset);
-                if let Some(point) = game.valid_add_set(Set::new(point, direction, offset)) {
-                    game.add_set(set, point);
-                    let mut present = game.sets.clone();
-                    present.sort_unstable_by(|s1, s2| s1.packed().cmp(&s2.packed()));
-                    if !gamestates[game.score() - 1]
-                        .iter()
-                        .any(|gs| v_eq(&present, gs))
-                    {
-                        // print!("c");
-                        gamestates[game.score() - 1].push(present);
-                        let num_possible_moves = game.possible_moves();
-                        possible_moves.insert((num_possible_moves, set, point));
-                    }
-                    game.remove_set(set, point);
-                }
-            }
-        }
-    }
-    let mut sorted_possible_moves = Vec::with_capacity(possible_moves.len());
-    for m in possible_moves.into_iter() {
-        sorted_possible_moves.push(m);
-    }
-    sorted_possible_moves.sort_unstable_by(cmp_quad_hpm);
-    for (_, set, point) in sorted_possible_moves.into_iter() {
-        game.add_set(set, point);
-        if game.score() >= DESIRED_SCORE {
-            return true;
-        }
-        if branch_highest_set_vec(gamestates, game) {
-            return true;
-        } else {
-            game.remove_set(set, point);
-        }
-    }
-    // Ran out of moves to try, and we didn't reach the requisite number, so return `false`.
-    false
+// Same as `base_highest_set`, but with gamestates stored as vecs sorted by `Set::packed`.
+pub fn base_highest_set_vec(
+    gamestates: &mut TranspositionTable<Vec<Set>>,
+    game: Game,
+    point: Point,
+    search: &mut Search
+) -> Game {
+    let method =
+        Method::new(v_sorted_eq, store_v_sorted, store_npm_s_p, cmp_quad_hpm, spm_intoiter);
+    base(gamestates, game, point, method, search)
 }
 
+#[allow(clippy::ptr_arg)]
 pub fn v_eq(v1: &Vec<Set>, v2: &Vec<Set>) -> bool {
     if v1.len() == v2.len() {
         for i in 0..v1.len() {
@@ -546,3 +746,11 @@ pub fn v_eq(v1: &Vec<Set>, v2: &Vec<Set>) -> bool {
         false
     }
 }
+
+
+// The stored gamestate is sorted by `Set::packed`, so each set can be looked up by binary search.
+#[allow(clippy::ptr_arg)]
+pub fn v_sorted_eq(sorted: &Vec<Set>, v: &Vec<Set>) -> bool {
+    sorted.len() == v.len()
+        && v.iter().all(|set| sorted.binary_search_by_key(&set.packed(), Set::packed).is_ok())
+}
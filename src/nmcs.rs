@@ -0,0 +1,109 @@
+use crate::game::Game;
+use crate::methods::Search;
+use crate::point::Point;
+use crate::set::Set;
+use crate::solvers::SolverConfig;
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg64Mcg;
+use std::time::Instant;
+
+// Nested Monte Carlo Search. At level 0 a position is scored by one uniformly random playout. At
+// level n every move of the position is tried with a level n - 1 search, and the first move of the
+// best sequence found so far is played, until there are no moves left. The same seed always gives
+// the same game.
+pub fn nmcs(start: Game, level: usize, seed: u64, config: SolverConfig) -> Search {
+    let mut search = Search::new(&start, &config, Instant::now());
+    let mut rng = Pcg64Mcg::seed_from_u64(seed);
+    if !search.stopped() {
+        nested(start, level, &mut rng, &mut search);
+    }
+    search.finish()
+}
+
+// Returns the moves of the best game found from `game`.
+fn nested(
+    mut game: Game,
+    level: usize,
+    rng: &mut Pcg64Mcg,
+    search: &mut Search
+) -> Vec<(Set, Point)> {
+    if level == 0 {
+        return playout(game, rng, search);
+    }
+    // Best sequence from the position this was called on, the first `played` moves of which have
+    // been made on `game`.
+    let mut best: Vec<(Set, Point)> = Vec::new();
+    let mut played = 0;
+    loop {
+        if search.stopped() {
+            break;
+        }
+        let moves = game.legal_moves();
+        if moves.is_empty() {
+            break;
+        }
+        for &(set, point) in moves.iter() {
+            let mut child = game.clone();
+            child.add_set(set, point);
+            search.count_node();
+            let sequence = nested(child, level - 1, rng, search);
+            if played + 1 + sequence.len() > best.len() {
+                best.truncate(played);
+                best.push((set, point));
+                best.extend(sequence);
+            }
+            if search.stopped() {
+                break;
+            }
+        }
+        let (set, point) = best[played];
+        game.add_set(set, point);
+        played += 1;
+    }
+    best
+}
+
+fn playout(mut game: Game, rng: &mut Pcg64Mcg, search: &mut Search) -> Vec<(Set, Point)> {
+    let mut sequence = Vec::new();
+    loop {
+        let moves = game.legal_moves();
+        if moves.is_empty() {
+            break;
+        }
+        let (set, point) = moves[rng.gen_range(0, moves.len())];
+        game.add_set(set, point);
+        search.count_node();
+        sequence.push((set, point));
+    }
+    search.record(&game);
+    sequence
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::budget::Termination;
+    use crate::fixtures::{assert_best_replays, assert_repeatable};
+
+    #[test]
+    fn same_seed_same_game() {
+        assert_repeatable(|start| nmcs(start, 1, 7, SolverConfig::default()));
+    }
+
+    #[test]
+    fn best_game_replays() {
+        assert_best_replays(|start| nmcs(start, 1, 0, SolverConfig::default()));
+    }
+
+    #[test]
+    fn stops_at_target() {
+        let whole = nmcs(Game::new(), 1, 0, SolverConfig::default());
+        assert_eq!(whole.termination(), Termination::Exhausted);
+        let target = whole.best().score() / 2;
+        let config = SolverConfig { target: Some(target), ..SolverConfig::default() };
+        let search = nmcs(Game::new(), 1, 0, config);
+        assert_eq!(search.termination(), Termination::Reached);
+        assert!(search.best().score() >= target);
+        assert!(search.nodes() < whole.nodes());
+    }
+}
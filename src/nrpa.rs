@@ -0,0 +1,231 @@
+use ahash::AHashMap;
+use crate::game::Game;
+use crate::methods::Search;
+use crate::point::Point;
+use crate::set::Set;
+use crate::solvers::SolverConfig;
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg64Mcg;
+use std::time::Instant;
+
+// Learned weight of every move code seen so far. Codes that aren't in the map weigh 0.
+pub type Policy = AHashMap<u64, f64>;
+
+#[derive(Copy, Clone, Debug)]
+pub struct NrpaConfig {
+    pub level: usize,
+    // Number of searches one level down that each level runs, adapting the policy after each.
+    pub iterations: usize,
+    pub learning_rate: f64,
+    pub seed: u64,
+}
+
+impl Default for NrpaConfig {
+    fn default() -> Self {
+        NrpaConfig {
+            level: 3,
+            iterations: 100,
+            learning_rate: 1.0,
+            seed: 0,
+        }
+    }
+}
+
+// Nested Rollout Policy Adaptation. Playouts pick each move with probability proportional to
+// `exp(policy[code])`, and after every search one level down the policy is pulled towards the best
+// sequence found at this level. The same config always gives the same game.
+pub fn nrpa(start: Game, nrpa: NrpaConfig, config: SolverConfig) -> Search {
+    let mut search = Search::new(&start, &config, Instant::now());
+    let mut rng = Pcg64Mcg::seed_from_u64(nrpa.seed);
+    if !search.stopped() {
+        nested(&start, nrpa.level, Policy::default(), nrpa, &mut rng, &mut search);
+    }
+    search.finish()
+}
+
+// Code of the move adding `set` and `point` to `game`: the set itself, with which of the eight
+// neighbours of the new point are already on the board xored into bits 48 to 55. Packed values of
+// different sets are less than 2^37 apart, so they never differ only in those bits and codes of
+// different moves can't collide.
+pub fn move_code(game: &Game, set: Set, point: Point) -> u64 {
+    let mut neighbourhood = 0;
+    for (i, &(dx, dy)) in NEIGHBOURS.iter().enumerate() {
+        let mut neighbour = point;
+        neighbour.step((dx, dy));
+        if game.points.contains_key(&neighbour) {
+            neighbourhood |= 1 << i;
+        }
+    }
+    set.packed() as u64 ^ (neighbourhood << 48)
+}
+
+const NEIGHBOURS: [(i16, i16); 8] = [
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+    (-1, 0),
+];
+
+// Returns the best sequence of moves found from `start`.
+fn nested(
+    start: &Game,
+    level: usize,
+    mut policy: Policy,
+    nrpa: NrpaConfig,
+    rng: &mut Pcg64Mcg,
+    search: &mut Search
+) -> Vec<(Set, Point)> {
+    if level == 0 {
+        return playout(start.clone(), &policy, rng, search);
+    }
+    let mut best = Vec::new();
+    for _ in 0..nrpa.iterations {
+        let sequence = nested(start, level - 1, policy.clone(), nrpa, rng, search);
+        if sequence.len() >= best.len() {
+            best = sequence;
+        }
+        if search.stopped() {
+            break;
+        }
+        adapt(start, &mut policy, &best, nrpa.learning_rate);
+    }
+    best
+}
+
+fn playout(
+    mut game: Game,
+    policy: &Policy,
+    rng: &mut Pcg64Mcg,
+    search: &mut Search
+) -> Vec<(Set, Point)> {
+    let mut sequence = Vec::new();
+    let mut weights = Vec::new();
+    loop {
+        let moves = game.legal_moves();
+        if moves.is_empty() {
+            break;
+        }
+        weights.clear();
+        weights.extend(moves.iter().map(|&(set, point)| {
+            weight(policy, move_code(&game, set, point))
+        }));
+        let mut pick = rng.gen::<f64>() * weights.iter().sum::<f64>();
+        let mut chosen = moves.len() - 1;
+        for (i, &weight) in weights.iter().enumerate() {
+            if pick < weight {
+                chosen = i;
+                break;
+            }
+            pick -= weight;
+        }
+        let (set, point) = moves[chosen];
+        game.add_set(set, point);
+        search.count_node();
+        sequence.push((set, point));
+    }
+    search.record(&game);
+    sequence
+}
+
+// Raises the weight of every move of `sequence` and lowers the others in proportion to how likely
+// the policy was to play them, replaying the sequence from `start`.
+fn adapt(start: &Game, policy: &mut Policy, sequence: &[(Set, Point)], learning_rate: f64) {
+    let old = policy.clone();
+    let mut game = start.clone();
+    for &(set, point) in sequence {
+        let moves = game.legal_moves();
+        let weights = moves
+            .iter()
+            .map(|&(set, point)| move_code(&game, set, point))
+            .map(|code| (code, weight(&old, code)))
+            .collect::<Vec<_>>();
+        let total = weights.iter().map(|&(_, weight)| weight).sum::<f64>();
+        *policy.entry(move_code(&game, set, point)).or_insert(0.0) += learning_rate;
+        for (code, weight) in weights {
+            *policy.entry(code).or_insert(0.0) -= learning_rate * weight / total;
+        }
+        game.add_set(set, point);
+    }
+}
+
+#[inline]
+fn weight(policy: &Policy, code: u64) -> f64 {
+    policy.get(&code).cloned().unwrap_or(0.0).exp()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{assert_best_replays, assert_repeatable};
+    use crate::rules::Rules;
+    use crate::set::Direction;
+
+    fn small() -> NrpaConfig {
+        NrpaConfig { level: 2, iterations: 5, ..NrpaConfig::default() }
+    }
+
+    #[test]
+    fn same_config_same_game() {
+        assert_repeatable(|start| nrpa(start, small(), SolverConfig::default()));
+    }
+
+    #[test]
+    fn best_game_replays() {
+        assert_best_replays(|start| nrpa(start, small(), SolverConfig::default()));
+    }
+
+    #[test]
+    fn adapt_shifts_weight_to_played_move() {
+        let start = Game::new();
+        let moves = start.legal_moves();
+        let (set, point) = moves[3];
+        let mut policy = Policy::default();
+        adapt(&start, &mut policy, &[(set, point)], 1.0);
+        // Starting from a uniform policy, the played move gains what the others lose between them.
+        let share = 1.0 / moves.len() as f64;
+        for &(other, other_point) in moves.iter() {
+            let learned = policy[&move_code(&start, other, other_point)];
+            let expected = if other == set { 1.0 - share } else { -share };
+            assert!((learned - expected).abs() < 1e-9, "{}: {}", other, learned);
+        }
+    }
+
+    #[test]
+    fn neighbourhood_changes_code() {
+        // Four points in a row, with the set over them adding a fifth at (4,0). Only the second
+        // game has a point next to it.
+        let row = (0..4).map(|x| Point::new(x, 0));
+        let set = Set::new(Point::new(0, 0), Direction::H, 0, 5);
+        let point = Point::new(4, 0);
+        let alone = Game::from_points(row.clone(), Rules::default());
+        let crowded = Game::from_points(row.chain(Some(Point::new(5, 1))), Rules::default());
+        assert_eq!(alone.valid_add_set(set), Some(point));
+        assert_eq!(crowded.valid_add_set(set), Some(point));
+        assert_ne!(move_code(&alone, set, point), move_code(&crowded, set, point));
+    }
+
+    #[test]
+    fn different_sets_never_collide() {
+        let mut rng = Pcg64Mcg::seed_from_u64(0);
+        let mut sets: AHashMap<u64, Set> = AHashMap::new();
+        for _ in 0..20 {
+            let mut game = Game::new();
+            loop {
+                let moves = game.legal_moves();
+                if moves.is_empty() {
+                    break;
+                }
+                for &(set, point) in moves.iter() {
+                    let code = move_code(&game, set, point);
+                    assert_eq!(*sets.entry(code).or_insert(set), set);
+                }
+                let (set, point) = moves[rng.gen_range(0, moves.len())];
+                game.add_set(set, point);
+            }
+        }
+    }
+}
@@ -0,0 +1,265 @@
+use crate::game::Game;
+use crate::point::Point;
+use crate::rules::Variant;
+use crate::set::{Direction, Set};
+use std::fmt::Write;
+use std::io::{self, ErrorKind};
+
+// Pentasol / morpionsolitaire.com move notation:
+//
+//     # Morpion 5T   <- optional, the rules the game was played under
+//     # comments
+//     (x,y)          <- reference point
+//     (x,y) D o      <- one line per move
+//
+// Coordinates are on screen, so y grows downwards. The reference point is the position of the
+// anchor dot of the starting configuration: the leftmost dot of its top row, which for the standard
+// cross is the top left corner of the top arm. Each move gives the new dot, the direction of the
+// set (`-`, `|`, `/` or `\`) and the offset of the new dot from the middle of the set, counting
+// positive to the right (or downwards for `|`).
+
+impl Game {
+    pub fn to_pentasol(&self) -> String {
+        // Shift everything so that all screen coordinates are positive.
+        let (min_x, max_y) = self.points().fold((i16::MAX, i16::MIN), |(min_x, max_y), point| {
+            (min_x.min(point.x), max_y.max(point.y))
+        });
+        let screen = |point: Point| (point.x - min_x, max_y - point.y);
+        let reference = screen(anchor(self.starting_points()));
+        let mut out = String::new();
+        writeln!(out, "# Morpion {}{}", self.rules.line_length, variant_letter(self.rules.variant))
+            .unwrap();
+        writeln!(out, "# {} moves", self.score()).unwrap();
+        writeln!(out, "({},{})", reference.0, reference.1).unwrap();
+        let mut replay = Game::from_points(self.starting_points().iter().cloned(), self.rules);
+        for &set in self.sets.iter() {
+            let point = replay
+                .valid_add_set(set)
+                .expect("Game contains a set that can't be replayed from its starting points.");
+            replay.add_set(set, point);
+            let index = set.points().position(|set_point| set_point == point).unwrap() as i16;
+            let (symbol, offset) = match set.direction {
+                Direction::H => ('-', index - middle(set.length)),
+                Direction::V => ('|', middle(set.length) - index),
+                Direction::SP => ('/', index - middle(set.length)),
+                Direction::SN => ('\\', index - middle(set.length)),
+            };
+            let (x, y) = screen(point);
+            writeln!(out, "({},{}) {} {:+}", x, y, symbol, offset).unwrap();
+        }
+        out
+    }
+
+    // Plays the moves of a Pentasol record on top of `start`, which sets both the rules and where
+    // the record's reference point lands. A record whose header names other rules is rejected.
+    pub fn from_pentasol(text: &str, start: Game) -> io::Result<Game> {
+        let anchor = anchor(start.starting_points());
+        let mut game = start;
+        let mut reference = None;
+        for (i, line) in text.lines().enumerate() {
+            if let Some((length, variant)) = parse_header(line) {
+                if (length, variant) != (game.rules.line_length, game.rules.variant) {
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidData,
+                        format!(
+                            "line {}: record is for Morpion {}{}, but the start is {}{}",
+                            i + 1,
+                            length,
+                            variant_letter(variant),
+                            game.rules.line_length,
+                            variant_letter(game.rules.variant)
+                        )
+                    ));
+                }
+                continue;
+            }
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = |what: &str| io::Error::new(
+                ErrorKind::InvalidData,
+                format!("line {}: {}: `{}`", i + 1, what, line)
+            );
+            let (coords, rest) = parse_coords(line).ok_or_else(|| invalid("expected `(x,y)`"))?;
+            let (ref_x, ref_y) = match reference {
+                Some(reference) => reference,
+                None => {
+                    if !rest.trim().is_empty() {
+                        return Err(invalid("expected a reference point"));
+                    }
+                    reference = Some(coords);
+                    continue;
+                },
+            };
+            let point = Point::new(anchor.x + coords.0 - ref_x, anchor.y - (coords.1 - ref_y));
+            let mut fields = rest.split_whitespace();
+            let (direction, offset) = match (fields.next(), fields.next(), fields.next()) {
+                (Some(direction), Some(offset), None) => (direction, offset),
+                _ => return Err(invalid("expected `(x,y) direction offset`")),
+            };
+            let offset = offset
+                .trim_start_matches('+')
+                .parse::<i16>()
+                .map_err(|_| invalid("bad offset"))?;
+            let length = game.rules.line_length;
+            let (direction, index) = match direction {
+                "-" => (Direction::H, middle(length) + offset),
+                "|" => (Direction::V, middle(length) - offset),
+                "/" => (Direction::SP, middle(length) + offset),
+                "\\" => (Direction::SN, middle(length) + offset),
+                _ => return Err(invalid("bad direction")),
+            };
+            if index < 0 || index >= length as i16 {
+                return Err(invalid("offset out of range"));
+            }
+            let set = Set::new(point, direction, index, length);
+            match game.check_add_set(set) {
+                Ok(new) if new == point => game.add_set(set, point),
+                Ok(new) => return Err(invalid(&format!("illegal move: set adds {}", new))),
+                Err(error) => return Err(invalid(&format!("illegal move: {}", error))),
+            }
+        }
+        Ok(game)
+    }
+}
+
+fn variant_letter(variant: Variant) -> char {
+    match variant {
+        Variant::Touching => 'T',
+        Variant::Disjoint => 'D',
+    }
+}
+
+// The line length and variant of a `# Morpion 5T` header. Other comments give `None`.
+fn parse_header(line: &str) -> Option<(u8, Variant)> {
+    let rules = line.trim().strip_prefix('#')?.trim().strip_prefix("Morpion ")?.trim();
+    let variant = match rules.chars().last()? {
+        'T' => Variant::Touching,
+        'D' => Variant::Disjoint,
+        _ => return None,
+    };
+    let length = rules[..rules.len() - 1].parse().ok()?;
+    Some((length, variant))
+}
+
+// Index of the middle point of a set, rounding down for even lengths.
+#[inline]
+fn middle(length: u8) -> i16 {
+    (length as i16 - 1) / 2
+}
+
+fn anchor(points: &[Point]) -> Point {
+    points
+        .iter()
+        .cloned()
+        .max_by_key(|point| (point.y, -point.x))
+        .unwrap_or_else(|| Point::new(0, 0))
+}
+
+// Splits `(x,y) rest` into the coordinates and the rest of the line.
+fn parse_coords(line: &str) -> Option<((i16, i16), &str)> {
+    let line = line.strip_prefix('(')?;
+    let close = line.find(')')?;
+    let mut coords = line[..close].split(',').map(|coord| coord.trim().parse::<i16>());
+    match (coords.next(), coords.next(), coords.next()) {
+        (Some(Ok(x)), Some(Ok(y)), None) => Some(((x, y), &line[close + 1..])),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Rules;
+
+    // The first moves of a 5T game on the standard cross, written out by hand. The reference point
+    // (30,30) is the top left dot of the top arm, which is (-1,4) on the board.
+    const RECORD: &str = "\
+# Morpion 5T
+(30,30)
+(30,34) | +2
+(31,33) - +2
+(30,35) | -2
+";
+
+    fn record_sets() -> Vec<Set> {
+        vec![
+            // Down the left side of the top arm, adding the dot below it at the bottom end.
+            Set::new(Point::new(-1, 0), Direction::V, 0, 5),
+            // Along the top of the left arm, adding the dot to its right.
+            Set::new(Point::new(0, 1), Direction::H, 4, 5),
+            // Up the left side of the bottom arm, adding the dot above it at the top end.
+            Set::new(Point::new(-1, -1), Direction::V, 4, 5),
+        ]
+    }
+
+    #[test]
+    fn reads_record() {
+        let game = Game::from_pentasol(RECORD, Game::new()).unwrap();
+        assert_eq!(game.sets(), &record_sets()[..]);
+    }
+
+    #[test]
+    fn writes_record() {
+        let game = Game::new().replay(record_sets()).unwrap();
+        // The board is shifted so that its leftmost dots have x = 0 and its top dots y = 0, which
+        // puts the reference point at (3,0).
+        let lines = game.to_pentasol().lines().skip(2).map(str::to_owned).collect::<Vec<_>>();
+        assert_eq!(lines, ["(3,0)", "(3,4) | +2", "(4,3) - +2", "(3,5) | -2"]);
+    }
+
+    #[test]
+    fn round_trip() {
+        for &rules in [Rules::default(), Rules::new(Variant::Disjoint, 4)].iter() {
+            let mut game = Game::with_rules(rules);
+            for _ in 0..20 {
+                match game.legal_moves().last() {
+                    Some(&(set, point)) => game.add_set(set, point),
+                    None => break,
+                }
+            }
+            let read = Game::from_pentasol(&game.to_pentasol(), Game::with_rules(rules)).unwrap();
+            assert_eq!(read.sets(), game.sets());
+        }
+    }
+
+    #[test]
+    fn header_must_match_rules() {
+        let rules = Rules::new(Variant::Touching, 4);
+        let mut game = Game::with_rules(rules);
+        for _ in 0..10 {
+            match game.legal_moves().last() {
+                Some(&(set, point)) => game.add_set(set, point),
+                None => break,
+            }
+        }
+        let record = game.to_pentasol();
+        assert!(Game::from_pentasol(&record, Game::with_rules(rules)).is_ok());
+        let error = Game::from_pentasol(&record, Game::new()).unwrap_err();
+        assert_eq!(error.to_string(), "line 1: record is for Morpion 4T, but the start is 5T");
+        // Other comments that start the same way aren't headers.
+        let text = format!("# Morpion solitaire\n{}", RECORD);
+        assert!(Game::from_pentasol(&text, Game::new()).is_ok());
+    }
+
+    #[test]
+    fn parse_errors() {
+        let errors = [
+            ("(30,30) | +2\n", "expected a reference point"),
+            ("(30,30)\n30,34 | +2\n", "expected `(x,y)`"),
+            ("(30,30)\n(30,34) |\n", "expected `(x,y) direction offset`"),
+            ("(30,30)\n(30,34) x +2\n", "bad direction"),
+            ("(30,30)\n(30,34) | two\n", "bad offset"),
+            ("(30,30)\n(30,34) | +3\n", "offset out of range"),
+            ("(30,30)\n(30,34) - +2\n", "illegal move"),
+            ("# Morpion 4T\n(30,30)\n(30,34) | +2\n", "record is for Morpion 4T"),
+            ("# Morpion 5D\n(30,30)\n", "record is for Morpion 5D"),
+        ];
+        for &(text, message) in errors.iter() {
+            let error = Game::from_pentasol(text, Game::new()).unwrap_err();
+            assert!(error.to_string().contains(message), "{}: {}", message, error);
+            assert_eq!(error.kind(), ErrorKind::InvalidData);
+        }
+    }
+}
@@ -1,7 +1,8 @@
+use serde::{Deserialize, Serialize};
 use std::fmt::{self, Display};
 use std::hash::{Hash, Hasher};
 
-#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+#[derive(Eq, PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct Point {
     pub(crate) x: i16,
     pub(crate) y: i16,
@@ -13,6 +14,16 @@ impl Point {
         Point { x, y }
     }
 
+    #[inline]
+    pub fn x(&self) -> i16 {
+        self.x
+    }
+
+    #[inline]
+    pub fn y(&self) -> i16 {
+        self.y
+    }
+
     #[inline]
     pub fn step(&mut self, d: (i16, i16)) {
         self.x += d.0;
@@ -0,0 +1,132 @@
+use std::sync::Mutex;
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+
+// Time between two reports of the same search.
+pub const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+// A report on a running search, covering every worker. Reports are sent to
+// `SolverConfig::progress`.
+#[derive(Copy, Clone, Debug)]
+pub struct Progress {
+    pub elapsed: Duration,
+    // Positions played so far.
+    pub nodes: usize,
+    // Positions played per second since the previous report.
+    pub nodes_per_second: f64,
+    // Deepest position any worker has reached so far, in sets played.
+    pub deepest: usize,
+    // Highest score so far, which for a depth-first search is also the deepest it has gone.
+    pub best: usize,
+    // Positions looked up in the transposition tables, and how many of them had been seen before.
+    pub lookups: usize,
+    pub hits: usize,
+}
+
+impl Progress {
+    pub fn hit_rate(&self) -> f64 {
+        if self.lookups == 0 {
+            0.0
+        } else {
+            self.hits as f64 / self.lookups as f64
+        }
+    }
+}
+
+// Counts that the workers of a search add to as they go, and that reports are made from.
+#[derive(Debug)]
+pub(crate) struct Tally {
+    send: Sender<Progress>,
+    started: Instant,
+    totals: Mutex<Totals>,
+}
+
+#[derive(Debug, Default)]
+struct Totals {
+    nodes: usize,
+    lookups: usize,
+    hits: usize,
+    best: usize,
+    deepest: usize,
+    // When the previous report was sent, and the node count it had.
+    reported_at: Duration,
+    reported_nodes: usize,
+}
+
+impl Tally {
+    // The first report comes `REPORT_INTERVAL` after this, even for a search resumed with
+    // `started` back in the past.
+    pub(crate) fn new(send: Sender<Progress>, started: Instant) -> Self {
+        let totals = Totals { reported_at: started.elapsed(), ..Totals::default() };
+        Tally {
+            send,
+            started,
+            totals: Mutex::new(totals),
+        }
+    }
+
+    // Starts the totals off from the counts of the search being resumed, which the speed in the
+    // next report leaves out.
+    pub(crate) fn resume(&self, nodes: usize, lookups: usize, hits: usize, best: usize) {
+        let mut totals = self.totals.lock().expect("Failed to get lock on progress totals.");
+        totals.nodes += nodes;
+        totals.lookups += lookups;
+        totals.hits += hits;
+        totals.best = totals.best.max(best);
+        totals.deepest = totals.deepest.max(best);
+        totals.reported_nodes = totals.nodes;
+    }
+
+    // Adds what a worker has counted since it last called this, and sends a report if one is due.
+    pub(crate) fn add(&self, nodes: usize, lookups: usize, hits: usize, best: usize, depth: usize) {
+        let mut totals = self.totals.lock().expect("Failed to get lock on progress totals.");
+        totals.nodes += nodes;
+        totals.lookups += lookups;
+        totals.hits += hits;
+        totals.best = totals.best.max(best);
+        totals.deepest = totals.deepest.max(depth);
+        let elapsed = self.started.elapsed();
+        if elapsed < totals.reported_at + REPORT_INTERVAL {
+            return;
+        }
+        let seconds = (elapsed - totals.reported_at).as_secs_f64();
+        let progress = Progress {
+            elapsed,
+            nodes: totals.nodes,
+            nodes_per_second: (totals.nodes - totals.reported_nodes) as f64 / seconds,
+            deepest: totals.deepest,
+            best: totals.best,
+            lookups: totals.lookups,
+            hits: totals.hits,
+        };
+        totals.reported_at = elapsed;
+        totals.reported_nodes = totals.nodes;
+        // Nobody listening any more isn't a reason to stop searching.
+        let _ = self.send.send(progress);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+    use std::thread::sleep;
+
+    #[test]
+    fn resumed_tally_counts_from_saved_search() {
+        let (send, recv) = channel();
+        // Resumed after an hour, which the first report mustn't average the speed over.
+        let started = Instant::now() - Duration::from_secs(3600);
+        let tally = Tally::new(send, started);
+        tally.resume(1_000_000, 500, 200, 40);
+        tally.add(10, 5, 2, 30, 20);
+        assert!(recv.try_recv().is_err());
+        sleep(REPORT_INTERVAL);
+        tally.add(10, 5, 2, 30, 35);
+        let progress = recv.try_recv().expect("No report after the interval.");
+        assert_eq!(progress.nodes, 1_000_020);
+        assert_eq!((progress.lookups, progress.hits), (510, 204));
+        assert_eq!((progress.best, progress.deepest), (40, 40));
+        assert!(progress.nodes_per_second <= 20.0);
+    }
+}
@@ -0,0 +1,137 @@
+use crate::game::Game;
+use crate::point::Point;
+use crate::set::Set;
+use std::error::Error;
+use std::fmt::{self, Display};
+
+// Why a set can't be added to a game.
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum MoveError {
+    // Every point of the set is already on the board.
+    NoNewPoint,
+    // More than one point of the set is missing from the board. The first two are given.
+    MultipleNewPoints(Point, Point),
+    // The point is already used by a set in the same direction in a way the rules don't allow.
+    Overlap(Point),
+    // The set doesn't have the number of points the rules ask for.
+    WrongLength(u8),
+}
+
+impl Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MoveError::NoNewPoint => write!(f, "set adds no new point"),
+            MoveError::MultipleNewPoints(first, second) => {
+                write!(f, "set adds more than one new point: {} and {}", first, second)
+            },
+            MoveError::Overlap(point) => {
+                write!(f, "set overlaps a set in the same direction at {}", point)
+            },
+            MoveError::WrongLength(length) => write!(f, "set has the wrong length ({})", length),
+        }
+    }
+}
+
+impl Error for MoveError {}
+
+// The first illegal move of a replayed sequence.
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct ReplayError {
+    // Index of the move into the sequence, counting from 0.
+    pub index: usize,
+    pub set: Set,
+    pub error: MoveError,
+}
+
+impl Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "move {} ({}): {}", self.index, self.set, self.error)
+    }
+}
+
+impl Error for ReplayError {}
+
+impl Game {
+    // The point `test` adds, or why it can't be added.
+    #[inline]
+    pub fn check_add_set(&self, test: Set) -> Result<Point, MoveError> {
+        if test.length != self.rules.line_length {
+            return Err(MoveError::WrongLength(test.length));
+        }
+        let mut new = None;
+        for (i, point) in test.points().enumerate() {
+            if let Some(&flags) = self.points.get(&point) {
+                if flags & self.rules.check_mask(test.direction, i as u8) > 0 {
+                    return Err(MoveError::Overlap(point));
+                }
+            } else if let Some(first) = new {
+                return Err(MoveError::MultipleNewPoints(first, point));
+            } else {
+                new = Some(point);
+            }
+        }
+        new.ok_or(MoveError::NoNewPoint)
+    }
+
+    // Adds `set` if it's legal, returning the point it added.
+    pub fn try_add_set(&mut self, set: Set) -> Result<Point, MoveError> {
+        let point = self.check_add_set(set)?;
+        self.add_set(set, point);
+        Ok(point)
+    }
+
+    // Plays `moves` in order on a copy of this game, stopping at the first illegal one.
+    pub fn replay<I: IntoIterator<Item = Set>>(&self, moves: I) -> Result<Game, ReplayError> {
+        let mut game = self.clone();
+        for (index, set) in moves.into_iter().enumerate() {
+            game.try_add_set(set).map_err(|error| ReplayError { index, set, error })?;
+        }
+        Ok(game)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Rules;
+    use crate::set::Direction;
+
+    // Four points in a row along the x axis, from (0,0) to (3,0), played under 5T.
+    fn row() -> Game {
+        Game::from_points((0..4).map(|x| Point::new(x, 0)), Rules::default())
+    }
+
+    fn horizontal(x: i16, length: u8) -> Set {
+        Set::new(Point::new(x, 0), Direction::H, 0, length)
+    }
+
+    #[test]
+    fn move_errors() {
+        let mut game = row();
+        assert_eq!(game.check_add_set(horizontal(0, 5)), Ok(Point::new(4, 0)));
+        assert_eq!(game.check_add_set(horizontal(0, 4)), Err(MoveError::WrongLength(4)));
+        assert_eq!(
+            game.check_add_set(horizontal(2, 5)),
+            Err(MoveError::MultipleNewPoints(Point::new(4, 0), Point::new(5, 0)))
+        );
+        game.add_set(horizontal(0, 5), Point::new(4, 0));
+        assert_eq!(game.check_add_set(horizontal(1, 5)), Err(MoveError::Overlap(Point::new(1, 0))));
+        let full = Game::from_points((0..5).map(|x| Point::new(x, 0)), Rules::default());
+        assert_eq!(full.check_add_set(horizontal(0, 5)), Err(MoveError::NoNewPoint));
+    }
+
+    #[test]
+    fn replay_reports_first_bad_move() {
+        let row = row();
+        let game = row.replay(vec![horizontal(0, 5)]).unwrap();
+        assert_eq!(game.sets(), &[horizontal(0, 5)]);
+        // Both the second and third moves are illegal, only the second is reported.
+        let moves = vec![horizontal(0, 5), horizontal(1, 5), horizontal(2, 5)];
+        let error = ReplayError {
+            index: 1,
+            set: horizontal(1, 5),
+            error: MoveError::Overlap(Point::new(1, 0)),
+        };
+        assert_eq!(row.replay(moves).err(), Some(error));
+    }
+}
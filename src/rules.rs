@@ -0,0 +1,93 @@
+use crate::set::Direction;
+use serde::{Deserialize, Serialize};
+
+// T ouching: parallel sets may share an endpoint (5T).
+// D isjoint: parallel sets may not share any point at all (5D).
+#[derive(Eq, PartialEq, Copy, Clone, Hash, Debug, Serialize, Deserialize)]
+pub enum Variant {
+    Touching,
+    Disjoint,
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Hash, Debug, Serialize, Deserialize)]
+pub struct Rules {
+    pub(crate) variant: Variant,
+    // Number of points in a set, e.g. 5 for 5T/5D, 4 for 4T/4D.
+    pub(crate) line_length: u8,
+}
+
+impl Rules {
+    // Shortest set that `new` accepts.
+    pub const MIN_LINE_LENGTH: u8 = 2;
+
+    #[inline]
+    pub fn new(variant: Variant, line_length: u8) -> Self {
+        assert!(line_length >= Rules::MIN_LINE_LENGTH, "Sets must have at least two points.");
+        Rules { variant, line_length }
+    }
+
+    #[inline]
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    #[inline]
+    pub fn line_length(&self) -> u8 {
+        self.line_length
+    }
+
+    // Mask checked against the flags of the `index`th point of a candidate set, counting from the
+    // start point. A point whose flags overlap its mask is already used by a parallel set in a way
+    // that rules out the candidate.
+    #[inline]
+    pub fn check_mask(&self, direction: Direction, index: u8) -> u8 {
+        match self.variant {
+            Variant::Touching => if index == 0 {
+                direction.get_out_mask()
+            } else if index == self.line_length - 1 {
+                direction.get_in_mask()
+            } else {
+                direction.get_inout_mask()
+            },
+            Variant::Disjoint => direction.get_inout_mask(),
+        }
+    }
+
+    // Range of offsets (see `Set::new`) at which a point with the given flags can sit in a new set
+    // going in `direction`, or `None` if it can't be part of one at all.
+    #[inline]
+    pub fn offsets(&self, flags: u8, direction: Direction) -> Option<(i16, i16)> {
+        let length = self.line_length as i16;
+        match self.variant {
+            Variant::Touching => if (flags & direction.get_inout_mask()).count_ones() == 2 {
+                // If this point is being used in and out in a given direction, it can no longer be
+                // the start or end point for a set.
+                None
+            } else if flags & direction.get_in_mask() > 0 {
+                // If this point is being used coming into the point from a given direction, this
+                // point and the next ones in the same direction can be part of a set.
+                Some((0, 1))
+            } else if flags & direction.get_out_mask() > 0 {
+                // If this point is being used going out of the point from a given direction, this
+                // point and the next ones in the same direction (but stepping backwards) can be
+                // part of a set.
+                Some((length - 1, length))
+            } else {
+                // This point can be the start, end, or in the middle of a set.
+                Some((0, length))
+            },
+            // Any use at all in a direction rules the point out for further sets in it.
+            Variant::Disjoint => if flags & direction.get_inout_mask() > 0 {
+                None
+            } else {
+                Some((0, length))
+            },
+        }
+    }
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules::new(Variant::Touching, 5)
+    }
+}
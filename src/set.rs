@@ -1,9 +1,10 @@
 use crate::point::Point;
+use serde::{Deserialize, Serialize};
 use std::fmt::{self, Display};
 use std::hash::{Hash, Hasher};
 use std::iter::Iterator;
 
-#[derive(Eq, PartialEq, Copy, Clone, Hash, Debug)]
+#[derive(Eq, PartialEq, Copy, Clone, Hash, Debug, Serialize, Deserialize)]
 // Directions:
 //  V |  / SP
 //    .  _ H
@@ -27,14 +28,12 @@ impl Direction {
         }
     }
 
+    // Step from the first to the last point of a set of `length` points.
     #[inline]
-    pub fn full_step(&self) -> (i16, i16) {
-        match self {
-            Direction::H => (4, 0),
-            Direction::V => (0, 4),
-            Direction::SP => (4, 4),
-            Direction::SN => (4, -4),
-        }
+    pub fn full_step(&self, length: u8) -> (i16, i16) {
+        let (x, y) = self.single_step();
+        let n = length as i16 - 1;
+        (x * n, y * n)
     }
 
     #[inline]
@@ -48,13 +47,10 @@ impl Direction {
     }
 
     #[inline]
-    pub fn opposite_full_step(&self) -> (i16, i16) {
-        match self {
-            Direction::H => (-4, 0),
-            Direction::V => (0, -4),
-            Direction::SP => (-4, -4),
-            Direction::SN => (-4, 4),
-        }
+    pub fn opposite_full_step(&self, length: u8) -> (i16, i16) {
+        let (x, y) = self.opposite_single_step();
+        let n = length as i16 - 1;
+        (x * n, y * n)
     }
 
     #[inline]
@@ -148,15 +144,16 @@ impl Direction {
     }
 }
 
-#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+#[derive(Eq, PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct Set {
     pub(crate) start_x: i16,
     pub(crate) start_y: i16,
     pub(crate) direction: Direction,
+    pub(crate) length: u8,
 }
 
 impl Set {
-    pub fn new(start: Point, direction: Direction, offset: i16) -> Self {
+    pub fn new(start: Point, direction: Direction, offset: i16, length: u8) -> Self {
         let Point {
             x: mut start_x,
             y: mut start_y,
@@ -168,23 +165,59 @@ impl Set {
             start_x,
             start_y,
             direction,
+            length,
         }
     }
 
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    pub fn length(&self) -> u8 {
+        self.length
+    }
+
     pub fn start_point(&self) -> Point {
         Point::new(self.start_x, self.start_y)
     }
 
+    pub fn end_point(&self) -> Point {
+        let mut end = self.start_point();
+        end.step(self.direction.full_step(self.length));
+        end
+    }
+
+    pub fn points(&self) -> SetIter {
+        let (dx, dy) = self.direction.single_step();
+        SetIter {
+            x: self.start_x,
+            y: self.start_y,
+            dx,
+            dy,
+            step: 0,
+            length: self.length,
+        }
+    }
+
     pub fn packed(&self) -> i64 {
         self.start_point().packed() as i64 + ((self.direction.set_in_t_mask() as i64) << 32)
     }
+
+    // Zobrist key of the set. The board has no fixed size to fill a table of random keys for, so
+    // the key is the SplitMix64 finaliser of the packed set instead, which scatters nearby sets
+    // just as well.
+    #[inline]
+    pub fn zobrist(&self) -> u64 {
+        let mut z = (self.packed() as u64).wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
 }
 
 impl Display for Set {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut end = self.start_point();
-        end.step(self.direction.full_step());
-        write!(f, "{} -> {}", self.start_point(), end)
+        write!(f, "{} -> {}", self.start_point(), self.end_point())
     }
 }
 
@@ -203,13 +236,14 @@ pub struct SetIter {
     dx: i16,
     dy: i16,
     step: u8,
+    length: u8,
 }
 
 impl Iterator for SetIter {
     type Item = Point;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.step < 5 {
+        if self.step < self.length {
             let out = Point::new(self.x, self.y);
             self.x += self.dx;
             self.y += self.dy;
@@ -1,85 +1,254 @@
-use crate::DESIRED_SCORE;
-use crate::game::{Game, STARTING_POINTS};
+use crate::checkpoint::{Checkpoint, Checkpointing, Gamestate, Pause, WorkerState};
+use crate::game::Game;
 use crate::methods::{
     base,
-    Method
+    branch,
+    Cancel,
+    Method,
+    resume_root,
+    Search,
+    TranspositionTable
 };
 use crate::point::Point;
+use crate::progress::Progress;
 use crate::set::Set;
+use crate::stealing::{Shared, SharedTable, Task};
 use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
 use std::hash::Hash;
+use std::io;
+use std::iter::once;
+use std::mem::take;
 use std::sync::{Arc, Mutex};
-use std::sync::mpsc::{channel, TryRecvError};
-use std::thread::spawn;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::{scope, spawn};
+use std::time::{Duration, Instant};
+use std::vec::IntoIter;
 
-pub fn multithreaded_method<MoveSet: 'static, MoveInfo: 'static, MoveIter: 'static>(
-    method: Method<MoveSet, MoveInfo, MoveIter>
-) -> Option<Game>
+// Signature of the fixed-method searches like `base_highest_set`.
+pub type BaseFn<MoveSet> = fn(&mut TranspositionTable<MoveSet>, Game, Point, &mut Search) -> Game;
+
+#[derive(Clone, Debug, Default)]
+pub struct SolverConfig {
+    // Stop as soon as a game reaches this score. `None` means there's no target, and the search
+    // keeps going for the highest score it can find.
+    pub target: Option<usize>,
+    // Number of worker threads for the multithreaded solvers. `None` uses one per CPU, and
+    // `Some(0)` is taken as one.
+    pub threads: Option<usize>,
+    // Checked all through the search. Once it's cancelled, the solver stops and returns the best
+    // game found so far.
+    pub cancel: Option<Cancel>,
+    // Where the depth-first solvers send a `Progress` report every `REPORT_INTERVAL` or so. The
+    // reports end when the solver returns.
+    pub progress: Option<Sender<Progress>>,
+    // Where and how often `multithreaded_method` and `singlethreaded_method` save a `Checkpoint`
+    // to resume the search from.
+    pub checkpoint: Option<Checkpointing>,
+    // Stop once the solver has run this long, or once it has played this many positions between
+    // all its workers, and return the best game so far. The Monte Carlo and beam solvers count
+    // every move of their playouts and expansions. `Search::termination` says which limit was hit.
+    pub time_limit: Option<Duration>,
+    pub node_limit: Option<usize>,
+}
+
+impl SolverConfig {
+    #[inline]
+    pub fn reached(&self, game: &Game) -> bool {
+        self.target.is_some_and(|target| game.score() >= target)
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads.unwrap_or_else(num_cpus::get).max(1)
+    }
+
+    pub fn thread_pool(&self) -> ThreadPool {
+        ThreadPoolBuilder::new()
+            .num_threads(self.threads())
+            .build()
+            .expect("Failed to build thread pool.")
+    }
+}
+
+// All solvers return the finished `Search`, whose best game is the highest scoring one found from
+// any root, whether or not it reached the target. Only `Game::root_points` are searched, one root
+// per orbit under the start's symmetries, and the search counts the rest as skipped.
+pub fn multithreaded_method<MoveSet, MoveInfo, MoveIter>(
+    method: Method<MoveSet, MoveInfo, MoveIter>,
+    start: Game,
+    config: SolverConfig
+) -> Search
+    where
+        MoveSet: 'static + Clone + Send + Gamestate,
+        MoveInfo: 'static + Hash + Eq + PartialEq,
+        MoveIter: 'static + Iterator<Item = (Set, Point)>
+{
+    let checkpoint = Checkpoint::new::<MoveSet>(&start);
+    run_multithreaded_method(method, start, checkpoint, config)
+}
+
+// Carries on with a search saved by `multithreaded_method` or `singlethreaded_method`, which can
+// have had a different number of threads. The roots that the saved threads were in the middle of
+// are shared out between the threads, which finish them first, one after the other. Fails if the
+// search was saved with a `method` that stores its positions differently.
+pub fn resume_multithreaded_method<MoveSet, MoveInfo, MoveIter>(
+    method: Method<MoveSet, MoveInfo, MoveIter>,
+    checkpoint: Checkpoint,
+    config: SolverConfig
+) -> io::Result<Search>
+    where
+        MoveSet: 'static + Clone + Send + Gamestate,
+        MoveInfo: 'static + Hash + Eq + PartialEq,
+        MoveIter: 'static + Iterator<Item = (Set, Point)>
+{
+    checkpoint.check_store::<MoveSet>()?;
+    Ok(run_multithreaded_method(method, checkpoint.start(), checkpoint, config))
+}
+
+// What a worker of `multithreaded_method` tells the thread collecting the results.
+enum Event {
+    Root(Box<Search>),
+    // The worker has stopped for a checkpoint, and waits for it to be saved. Its own state comes
+    // first, followed by the roots it has taken over from saved threads but not got to yet.
+    Suspended(Vec<WorkerState>),
+    // The search has been cut short, and the worker has exited, leaving its state like
+    // `Suspended` does for the checkpoint saved once every worker has stopped.
+    Stopped(Vec<WorkerState>),
+    Exit,
+}
+
+fn run_multithreaded_method<MoveSet, MoveInfo, MoveIter>(
+    method: Method<MoveSet, MoveInfo, MoveIter>,
+    start: Game,
+    checkpoint: Checkpoint,
+    config: SolverConfig
+) -> Search
     where
-        MoveSet: Clone + Send,
-        MoveInfo: Hash + Eq + PartialEq,
-        MoveIter: Iterator<Item = (Set, Point)>
+        MoveSet: 'static + Clone + Send + Gamestate,
+        MoveInfo: 'static + Hash + Eq + PartialEq,
+        MoveIter: 'static + Iterator<Item = (Set, Point)>
 {
     let (send, recv) = channel();
-    let starting_points = Arc::new(Mutex::new(STARTING_POINTS.iter().cloned()));
-    for i in 0..num_cpus::get() {
-        let mut t_gamestates: Vec<Vec<MoveSet>> = vec![Vec::new(); DESIRED_SCORE];
+    let mut overall = Search::new(&start, &config, checkpoint.started());
+    checkpoint.resume_progress(&overall);
+    overall.restore(checkpoint.finished, &start);
+    overall.pause = config
+        .checkpoint
+        .as_ref()
+        .map(|checkpointing| Arc::new(Pause::new(checkpointing.interval)));
+    let starting_points = Arc::new(Mutex::new(checkpoint.roots.into_iter()));
+    // At least one, so every saved worker has a thread to go to.
+    let threads = config.threads();
+    let mut shares = (0..threads).map(|_| Vec::new()).collect::<Vec<_>>();
+    for (i, worker) in checkpoint.workers.into_iter().enumerate() {
+        shares[i % threads].push(worker);
+    }
+    let mut workers = Vec::new();
+    for share in shares {
+        let mut t_gamestates: TranspositionTable<MoveSet> = TranspositionTable::default();
+        let mut t_pending = share
+            .into_iter()
+            .filter_map(|worker| worker.restore(&mut t_gamestates))
+            .collect::<Vec<_>>();
         let t_send = send.clone();
         let t_starting_points = starting_points.clone();
-        spawn(move || {
+        let t_start = start.clone();
+        let t_search = overall.split(&start);
+        workers.push(spawn(move || {
             loop {
-                let point = {
-                    let mut lock = t_starting_points
-                        .lock()
-                        .expect("Failed to get lock on starting points iterator.");
-                    lock.next()
-                };
-                if let Some(point) = point {
-                    t_send.send((
-                        base(&mut t_gamestates, Game::new(), point, method),
-                        t_gamestates.clone()
-                    )).unwrap();
+                let mut search = t_search.split(&t_start);
+                let mut game = if let Some(state) = t_pending.pop() {
+                    let path = search.restore(state, &t_start);
+                    resume_root(&mut t_gamestates, &t_start, &path, method, &mut search)
                 } else {
-                    break;
+                    if t_search.stopped() {
+                        break;
+                    }
+                    if let Some(pause) = &t_search.pause {
+                        pause.poll();
+                        if pause.requested() {
+                            let saved = pause.saved();
+                            let state = WorkerState::save(&t_gamestates, None, &t_start);
+                            if t_send.send(Event::Suspended(vec![state])).is_err() {
+                                break;
+                            }
+                            pause.wait(saved);
+                        }
+                    }
+                    let point = {
+                        let mut lock = t_starting_points
+                            .lock()
+                            .expect("Failed to get lock on starting points iterator.");
+                        lock.next()
+                    };
+                    match point {
+                        Some(point) => {
+                            base(&mut t_gamestates, t_start.clone(), point, method, &mut search)
+                        },
+                        None => break,
+                    }
+                };
+                while search.suspended {
+                    let pause = search.pause.clone().expect("Suspended without a pause.");
+                    let saved = pause.saved();
+                    let state = WorkerState::save(&t_gamestates, Some((&search, &game)), &t_start);
+                    let states = once(state)
+                        .chain(t_pending.iter().cloned().map(WorkerState::pending))
+                        .collect();
+                    // Cut short rather than paused, so there's no picking the root up again.
+                    if search.stopped() {
+                        let _ = t_send.send(Event::Stopped(states));
+                        return;
+                    }
+                    if t_send.send(Event::Suspended(states)).is_err() {
+                        return;
+                    }
+                    pause.wait(saved);
+                    search.suspended = false;
+                    let path = game.sets()[t_start.score()..].to_vec();
+                    game = resume_root(&mut t_gamestates, &t_start, &path, method, &mut search);
+                }
+                if t_send.send(Event::Root(Box::new(search))).is_err() {
+                    return;
                 }
             }
-        });
-        println!("Starting {}", i);
+            // Stopped between roots, the table is all there is to save.
+            let _ = t_send.send(if t_search.cut_short() {
+                Event::Stopped(vec![WorkerState::save(&t_gamestates, None, &t_start)])
+            } else {
+                Event::Exit
+            });
+        }));
     }
     drop(send);
-    let mut try_best = None;
-    loop {
-        match recv.try_recv() {
-            Ok((game, gamestates)) => if game.score() >= DESIRED_SCORE {
-                try_best = Some(game);
-                let mut num_gamestates = 0;
-                for (i, gamestates_of_size_n) in gamestates.iter().enumerate() {
-                    println!("{}: {}", i + 1, gamestates_of_size_n.len());
-                    num_gamestates += gamestates_of_size_n.len();
-                }
-                println!("Total: {}", num_gamestates);
-                break;
-            } else {
-                continue;
-            },
-            Err(TryRecvError::Disconnected) => break,
-            _ => continue
-        }
+    let overall =
+        collect(recv, overall, &start, Some(MoveSet::STORE), &starting_points, &config, threads);
+    for worker in workers {
+        worker.join().expect("Worker thread panicked.");
     }
-    try_best
+    overall.finish()
 }
 
 pub fn multithreaded<MoveSet: 'static + Clone + Send>(
-    base: fn(&mut Vec<Vec<MoveSet>>, Game, Point) -> Game
-) -> Option<Game> {
+    base: BaseFn<MoveSet>,
+    start: Game,
+    config: SolverConfig
+) -> Search {
+    let started = Instant::now();
     let (send, recv) = channel();
-    let starting_points = Arc::new(Mutex::new(STARTING_POINTS.iter().cloned()));
-    for i in 0..num_cpus::get() {
-        let mut t_gamestates: Vec<Vec<MoveSet>> = vec![Vec::new(); DESIRED_SCORE];
+    let roots = start.root_points();
+    let mut overall = Search::new(&start, &config, started);
+    overall.skipped_roots = start.starting_points().len() - roots.len();
+    let starting_points = Arc::new(Mutex::new(roots.into_iter()));
+    let mut workers = Vec::new();
+    for _ in 0..config.threads() {
+        let mut t_gamestates: TranspositionTable<MoveSet> = TranspositionTable::default();
         let t_send = send.clone();
         let t_starting_points = starting_points.clone();
-        spawn(move || {
-            loop {
+        let t_start = start.clone();
+        let t_search = overall.split(&start);
+        workers.push(spawn(move || {
+            while !t_search.stopped() {
                 let point = {
                     let mut lock = t_starting_points
                         .lock()
@@ -87,88 +256,577 @@ pub fn multithreaded<MoveSet: 'static + Clone + Send>(
                     lock.next()
                 };
                 if let Some(point) = point {
-                    t_send.send((
-                        base(&mut t_gamestates, Game::new(), point),
-                        t_gamestates.clone()
-                    )).unwrap();
+                    let mut search = t_search.split(&t_start);
+                    base(&mut t_gamestates, t_start.clone(), point, &mut search);
+                    if t_send.send(Event::Root(Box::new(search))).is_err() {
+                        return;
+                    }
                 } else {
                     break;
                 }
             }
-        });
-        println!("Starting {}", i);
+            let _ = t_send.send(Event::Exit);
+        }));
     }
     drop(send);
-    let mut try_best = None;
-    loop {
-        match recv.try_recv() {
-            Ok((game, gamestates)) => if game.score() >= DESIRED_SCORE {
-                try_best = Some(game);
-                let mut num_gamestates = 0;
-                for (i, gamestates_of_size_n) in gamestates.iter().enumerate() {
-                    println!("{}: {}", i + 1, gamestates_of_size_n.len());
-                    num_gamestates += gamestates_of_size_n.len();
-                }
-                println!("Total: {}", num_gamestates);
-                break;
-            } else {
-                continue;
+    let threads = workers.len();
+    // Workers without a pause never suspend, so there are no tables to save.
+    let overall = collect(recv, overall, &start, None, &starting_points, &config, threads);
+    for worker in workers {
+        worker.join().expect("Worker thread panicked.");
+    }
+    overall.finish()
+}
+
+// Merges the search of each root as it comes in, until every worker has finished. Workers stop
+// by themselves once the search has been stopped. Once every worker still running has suspended
+// itself for a checkpoint, the checkpoint is saved along with the `Gamestate::STORE` of their
+// tables, and they carry on.
+// Saves `checkpoint` for `overall`, which carries on if that fails, noting the error.
+fn save(checkpoint: Checkpoint, checkpointing: &Checkpointing, overall: &mut Search) {
+    if let Err(error) = checkpoint.save(&checkpointing.path) {
+        overall.checkpoint_error = Some(error.to_string());
+    }
+}
+
+fn collect(
+    recv: Receiver<Event>,
+    mut overall: Search,
+    start: &Game,
+    store: Option<&str>,
+    roots: &Mutex<IntoIter<Point>>,
+    config: &SolverConfig,
+    mut live: usize
+) -> Search {
+    let mut suspended = Vec::new();
+    let mut stopped = Vec::new();
+    let mut waiting = 0;
+    for event in recv {
+        match event {
+            Event::Root(search) => overall.merge(*search),
+            Event::Suspended(states) => {
+                suspended.extend(states);
+                waiting += 1;
             },
-            Err(TryRecvError::Disconnected) => break,
-            _ => continue
+            Event::Stopped(states) => {
+                stopped.extend(states);
+                live -= 1;
+            },
+            Event::Exit => live -= 1,
+        }
+        if waiting == 0 || waiting < live {
+            continue;
         }
+        waiting = 0;
+        let workers = take(&mut suspended);
+        // Once the search has stopped, the workers let go here stop too, and are saved below.
+        if !overall.stopped() {
+            let checkpointing =
+                config.checkpoint.as_ref().expect("Suspended without a checkpoint.");
+            let store = store.expect("Suspended without a store.");
+            let checkpoint = Checkpoint::of(start, store, &overall, remaining(roots), workers);
+            save(checkpoint, checkpointing, &mut overall);
+        }
+        overall.pause.as_ref().expect("Suspended without a pause.").release();
+    }
+    // Roots the workers were cut short in count with what they had found by then.
+    let cut = stopped.iter().filter_map(|worker| worker.search.clone()).collect::<Vec<_>>();
+    if overall.cut_short() {
+        let checkpointing = config.checkpoint.as_ref().expect("Cut short without a checkpoint.");
+        let store = store.expect("Cut short without a store.");
+        let checkpoint = Checkpoint::of(start, store, &overall, remaining(roots), stopped);
+        save(checkpoint, checkpointing, &mut overall);
+    }
+    for state in cut {
+        let mut search = overall.split(start);
+        search.restore(state, start);
+        search.finish_root();
+        overall.merge(search);
     }
-    try_best
+    overall
 }
 
-pub fn multithreaded_rayon_method<MoveSet: 'static, MoveInfo: 'static, MoveIter: 'static>(
-    method: Method<MoveSet, MoveInfo, MoveIter>
-) -> Option<Game>
+// Roots no worker has started.
+fn remaining(roots: &Mutex<IntoIter<Point>>) -> Vec<Point> {
+    roots.lock().expect("Failed to get lock on starting points iterator.").as_slice().to_vec()
+}
+
+pub fn multithreaded_rayon_method<MoveSet, MoveInfo, MoveIter>(
+    method: Method<MoveSet, MoveInfo, MoveIter>,
+    start: Game,
+    config: SolverConfig
+) -> Search
     where
-        MoveSet: Clone + Send,
-        MoveInfo: Hash + Eq + PartialEq,
-        MoveIter: Iterator<Item = (Set, Point)>
+        MoveSet: 'static + Clone + Send,
+        MoveInfo: 'static + Hash + Eq + PartialEq,
+        MoveIter: 'static + Iterator<Item = (Set, Point)>
 {
-    STARTING_POINTS
-        .par_iter()
-        .map(|&point| base(&mut vec![Vec::new(); DESIRED_SCORE], Game::new(), point, method))
-        .find_first(|game| game.score() >= DESIRED_SCORE)
+    multithreaded_rayon_roots(&start, config, |point, search| {
+        base(&mut TranspositionTable::default(), start.clone(), point, method, search);
+    })
 }
 
 pub fn multithreaded_rayon<MoveSet>(
-    base: fn(&mut Vec<Vec<MoveSet>>, Game, Point) -> Game
-) -> Option<Game>
+    base: BaseFn<MoveSet>,
+    start: Game,
+    config: SolverConfig
+) -> Search
     where MoveSet: Clone
 {
-    STARTING_POINTS
-        .par_iter()
-        .map(|&point| base(&mut vec![Vec::new(); DESIRED_SCORE], Game::new(), point))
-        .find_first(|game| game.score() >= DESIRED_SCORE)
+    multithreaded_rayon_roots(&start, config, |point, search| {
+        base(&mut TranspositionTable::default(), start.clone(), point, search);
+    })
+}
+
+// Searches every root in parallel, without starting any more roots once the search has been
+// stopped.
+fn multithreaded_rayon_roots<F>(start: &Game, config: SolverConfig, search_root: F) -> Search
+    where F: Fn(Point, &mut Search) + Sync
+{
+    let roots = start.root_points();
+    let mut overall = Search::new(start, &config, Instant::now());
+    overall.skipped_roots = start.starting_points().len() - roots.len();
+    let search = config.thread_pool().install(|| {
+        roots
+            .par_iter()
+            .filter_map(|&point| {
+                if overall.stopped() {
+                    return None;
+                }
+                let mut search = overall.split(start);
+                search_root(point, &mut search);
+                Some(search)
+            })
+            .reduce_with(|mut search, other| {
+                search.merge(other);
+                search
+            })
+    });
+    if let Some(search) = search {
+        overall.merge(search);
+    }
+    overall.finish()
 }
 
-pub fn singlethreaded_method<MoveSet: 'static, MoveInfo: 'static, MoveIter: 'static>(
-    method: Method<MoveSet, MoveInfo, MoveIter>
-) -> Option<Game>
+// Depth-first search where a worker that runs out of work takes over moves that a busy worker
+// hasn't tried yet, from as close to the root as possible, so every thread stays busy until the
+// whole tree has been searched. All the workers share one transposition table. The outcome of a
+// root only covers the part of it searched by the worker that started it, while `Search::nodes`
+// counts the nodes of every worker.
+pub fn work_stealing_method<MoveSet, MoveInfo, MoveIter>(
+    method: Method<MoveSet, MoveInfo, MoveIter>,
+    start: Game,
+    config: SolverConfig
+) -> Search
     where
-        MoveSet: Clone + Send,
-        MoveInfo: Hash + Eq + PartialEq,
-        MoveIter: Iterator<Item = (Set, Point)>
+        MoveSet: 'static + Clone + Send,
+        MoveInfo: 'static + Hash + Eq + PartialEq,
+        MoveIter: 'static + Iterator<Item = (Set, Point)>
 {
-    let mut gamestates = vec![Vec::new(); DESIRED_SCORE];
-    STARTING_POINTS
-        .iter()
-        .map(|&point| base(&mut gamestates, Game::new(), point, method))
-        .filter(|game| game.score() >= DESIRED_SCORE)
-        .next()
+    let roots = start.root_points();
+    let mut overall = Search::new(&start, &config, Instant::now());
+    overall.skipped_roots = start.starting_points().len() - roots.len();
+    let tasks = roots.into_iter().map(Task::Root).collect();
+    let shared = Arc::new(Shared::new(tasks, config.threads()));
+    let table = SharedTable::new();
+    let searches = scope(|s| {
+        let workers = (0..config.threads())
+            .map(|_| s.spawn(|| {
+                let mut gamestates = &table;
+                let mut search = overall.split(&start);
+                search.stealing = Some(shared.clone());
+                while let Some(task) = shared.next() {
+                    match task {
+                        Task::Root(point) => {
+                            base(&mut gamestates, start.clone(), point, method, &mut search);
+                        },
+                        Task::Subtree(sets) => {
+                            let mut game = start
+                                .replay(sets)
+                                .expect("Donated moves can't be replayed from the start.");
+                            // The worker that gave the move away never played it.
+                            search.count_node();
+                            search.record(&game);
+                            if !search.reached(&game) {
+                                branch(&mut gamestates, &mut game, method, &mut search);
+                            }
+                        },
+                    }
+                    if search.stopped() {
+                        shared.finish();
+                    }
+                }
+                search.stealing = None;
+                search
+            }))
+            .collect::<Vec<_>>();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().expect("Worker thread panicked."))
+            .collect::<Vec<_>>()
+    });
+    for search in searches {
+        overall.merge(search);
+    }
+    overall.finish()
+}
+
+pub fn singlethreaded_method<MoveSet, MoveInfo, MoveIter>(
+    method: Method<MoveSet, MoveInfo, MoveIter>,
+    start: Game,
+    config: SolverConfig
+) -> Search
+    where
+        MoveSet: 'static + Clone + Send + Gamestate,
+        MoveInfo: 'static + Hash + Eq + PartialEq,
+        MoveIter: 'static + Iterator<Item = (Set, Point)>
+{
+    let checkpoint = Checkpoint::new::<MoveSet>(&start);
+    run_singlethreaded_method(method, start, checkpoint, config)
+}
+
+// Carries on with a search saved by `multithreaded_method` or `singlethreaded_method`. The roots
+// that the threads were in the middle of are finished first, one after the other, and all their
+// transposition tables are merged into one. Fails like `resume_multithreaded_method`.
+pub fn resume_singlethreaded_method<MoveSet, MoveInfo, MoveIter>(
+    method: Method<MoveSet, MoveInfo, MoveIter>,
+    checkpoint: Checkpoint,
+    config: SolverConfig
+) -> io::Result<Search>
+    where
+        MoveSet: 'static + Clone + Send + Gamestate,
+        MoveInfo: 'static + Hash + Eq + PartialEq,
+        MoveIter: 'static + Iterator<Item = (Set, Point)>
+{
+    checkpoint.check_store::<MoveSet>()?;
+    Ok(run_singlethreaded_method(method, checkpoint.start(), checkpoint, config))
+}
+
+fn run_singlethreaded_method<MoveSet, MoveInfo, MoveIter>(
+    method: Method<MoveSet, MoveInfo, MoveIter>,
+    start: Game,
+    checkpoint: Checkpoint,
+    config: SolverConfig
+) -> Search
+    where
+        MoveSet: 'static + Clone + Send + Gamestate,
+        MoveInfo: 'static + Hash + Eq + PartialEq,
+        MoveIter: 'static + Iterator<Item = (Set, Point)>
+{
+    let mut gamestates = TranspositionTable::default();
+    let mut overall = Search::new(&start, &config, checkpoint.started());
+    checkpoint.resume_progress(&overall);
+    overall.restore(checkpoint.finished, &start);
+    overall.pause = config
+        .checkpoint
+        .as_ref()
+        .map(|checkpointing| Arc::new(Pause::new(checkpointing.interval)));
+    let mut pending = checkpoint
+        .workers
+        .into_iter()
+        .filter_map(|worker| worker.restore(&mut gamestates))
+        .collect::<Vec<_>>();
+    let mut roots = checkpoint.roots.into_iter();
+    let mut cut = None;
+    while !overall.stopped() {
+        let mut search = overall.split(&start);
+        let mut game = if let Some(state) = pending.pop() {
+            let path = search.restore(state, &start);
+            resume_root(&mut gamestates, &start, &path, method, &mut search)
+        } else if let Some(point) = roots.next() {
+            if let Some(pause) = &search.pause {
+                pause.poll();
+            }
+            base(&mut gamestates, start.clone(), point, method, &mut search)
+        } else {
+            break;
+        };
+        while search.suspended {
+            // Cut short rather than paused, so the root is saved below along with the rest.
+            if search.stopped() {
+                cut = Some(WorkerState::save(&gamestates, Some((&search, &game)), &start));
+                break;
+            }
+            let checkpointing =
+                config.checkpoint.as_ref().expect("Suspended without a checkpoint.");
+            // The roots still pending were left by other threads, so they go in as workers without
+            // a table of their own.
+            let workers = once(WorkerState::save(&gamestates, Some((&search, &game)), &start))
+                .chain(pending.iter().cloned().map(WorkerState::pending))
+                .collect();
+            let roots = roots.as_slice().to_vec();
+            let checkpoint = Checkpoint::of(&start, MoveSet::STORE, &overall, roots, workers);
+            save(checkpoint, checkpointing, &mut overall);
+            search.pause.as_ref().expect("Suspended without a pause.").release();
+            search.suspended = false;
+            let path = game.sets()[start.score()..].to_vec();
+            game = resume_root(&mut gamestates, &start, &path, method, &mut search);
+        }
+        if cut.is_some() {
+            break;
+        }
+        overall.merge(search);
+    }
+    // A search cut short saves where it got to, so that resuming it loses nothing.
+    if overall.cut_short() {
+        let checkpointing = config.checkpoint.as_ref().expect("Cut short without a checkpoint.");
+        let worker = cut.unwrap_or_else(|| WorkerState::save(&gamestates, None, &start));
+        let workers = once(worker)
+            .chain(pending.into_iter().map(WorkerState::pending))
+            .collect::<Vec<_>>();
+        pending = workers.iter().filter_map(|worker| worker.search.clone()).collect();
+        let roots = roots.as_slice().to_vec();
+        let checkpoint = Checkpoint::of(&start, MoveSet::STORE, &overall, roots, workers);
+        save(checkpoint, checkpointing, &mut overall);
+    } else {
+        pending.extend(cut.and_then(|worker| worker.search));
+    }
+    // Roots left by other threads, or cut short, that the search stopped before getting back to
+    // still count, with whatever they had found when they were saved.
+    for state in pending {
+        let mut search = overall.split(&start);
+        search.restore(state, &start);
+        search.finish_root();
+        overall.merge(search);
+    }
+    overall.finish()
 }
 
 pub fn singlethreaded<MoveSet: Clone>(
-    base: fn(&mut Vec<Vec<MoveSet>>, Game, Point) -> Game
-) -> Option<Game> {
-    let mut gamestates = vec![Vec::new(); DESIRED_SCORE];
-    STARTING_POINTS
-        .iter()
-        .map(|&point| base(&mut gamestates, Game::new(), point))
-        .filter(|game| game.score() >= DESIRED_SCORE)
-        .next()
-}
\ No newline at end of file
+    base: BaseFn<MoveSet>,
+    start: Game,
+    config: SolverConfig
+) -> Search {
+    let mut gamestates = TranspositionTable::default();
+    let mut search = Search::new(&start, &config, Instant::now());
+    let roots = start.root_points();
+    search.skipped_roots = start.starting_points().len() - roots.len();
+    for point in roots {
+        if search.stopped() {
+            break;
+        }
+        base(&mut gamestates, start.clone(), point, &mut search);
+    }
+    search.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::budget::Termination;
+    use crate::methods::{
+        cmp_hpm,
+        spm_intoiter_rev,
+        store_hs,
+        store_npm_s_p,
+        store_v_sorted,
+        v_hs_eq,
+        v_sorted_eq
+    };
+    use crate::rules::Rules;
+    use std::env::temp_dir;
+    use std::fs::remove_file;
+    use std::process;
+    use std::thread::sleep;
+
+    // A 4x4 square missing two opposite corners, small enough to search all of in a moment.
+    fn start() -> Game {
+        let points = (0..16)
+            .map(|i| Point::new(i % 4, i / 4))
+            .filter(|point| point != &Point::new(0, 0) && point != &Point::new(3, 3));
+        Game::from_points(points, Rules::default())
+    }
+
+    // Saves a checkpoint every time the search polls, and stops after `nodes` positions.
+    fn interrupted(name: &str, threads: usize, nodes: usize) -> SolverConfig {
+        let path = temp_dir().join(format!("dots-{}-{}.checkpoint", name, process::id()));
+        SolverConfig {
+            threads: Some(threads),
+            checkpoint: Some(Checkpointing { path, interval: Duration::ZERO }),
+            node_limit: Some(nodes),
+            ..SolverConfig::default()
+        }
+    }
+
+    fn roots(search: &Search) -> Vec<Point> {
+        let mut roots = search.outcomes.iter().map(|outcome| outcome.root).collect::<Vec<_>>();
+        roots.sort_unstable_by_key(Point::packed);
+        roots
+    }
+
+    #[test]
+    fn resumed_singlethreaded_search_matches_uninterrupted() {
+        let method = Method::new(v_hs_eq, store_hs, store_npm_s_p, cmp_hpm, spm_intoiter_rev);
+        let whole = singlethreaded_method(method, start(), SolverConfig::default());
+        assert_eq!(whole.termination, Termination::Exhausted);
+        // Stops and resumes again and again until the search is done.
+        let config = interrupted("single", 1, 1500);
+        let path = config.checkpoint.clone().unwrap().path;
+        let mut search = singlethreaded_method(method, start(), config.clone());
+        while search.termination == Termination::NodeLimit {
+            let checkpoint = Checkpoint::load(&path).expect("Failed to load checkpoint.");
+            search = resume_singlethreaded_method(method, checkpoint, config.clone()).unwrap();
+        }
+        remove_file(&path).expect("Failed to remove checkpoint.");
+        assert_eq!(search.termination, Termination::Exhausted);
+        assert_eq!(search.best.score(), whole.best.score());
+        assert_eq!(search.nodes, whole.nodes);
+        assert_eq!((search.lookups, search.hits), (whole.lookups, whole.hits));
+        let scores = |search: &Search| {
+            search.outcomes.iter().map(|outcome| (outcome.root, outcome.score)).collect::<Vec<_>>()
+        };
+        assert_eq!(scores(&search), scores(&whole));
+    }
+
+    #[test]
+    fn resumed_multithreaded_search_matches_uninterrupted() {
+        let method = Method::new(v_hs_eq, store_hs, store_npm_s_p, cmp_hpm, spm_intoiter_rev);
+        let whole = singlethreaded_method(method, start(), SolverConfig::default());
+        // Late enough for both threads to be saved in the middle of a root.
+        let config = interrupted("threads", 2, 2500);
+        let path = config.checkpoint.clone().unwrap().path;
+        let search = multithreaded_method(method, start(), config);
+        assert_eq!(search.termination, Termination::NodeLimit);
+        // Both saved threads are taken over by the one thread of the resumed search.
+        let checkpoint = Checkpoint::load(&path).expect("Failed to load checkpoint.");
+        remove_file(&path).expect("Failed to remove checkpoint.");
+        assert_eq!(checkpoint.workers.len(), 2);
+        let config = SolverConfig { threads: Some(1), ..SolverConfig::default() };
+        let search = resume_multithreaded_method(method, checkpoint, config).unwrap();
+        assert_eq!(search.termination, Termination::Exhausted);
+        assert_eq!(search.best.score(), whole.best.score());
+        assert_eq!(roots(&search), roots(&whole));
+    }
+
+    #[test]
+    fn search_cut_short_saves_where_it_got_to() {
+        let method = Method::new(v_hs_eq, store_hs, store_npm_s_p, cmp_hpm, spm_intoiter_rev);
+        let whole = singlethreaded_method(method, start(), SolverConfig::default());
+        for &threads in [1, 2].iter() {
+            // Far too long between checkpoints for any but the one saved on stopping.
+            let mut config = interrupted("cut", threads, 2000);
+            let path = config.checkpoint.clone().unwrap().path;
+            config.checkpoint.as_mut().unwrap().interval = Duration::from_secs(3600);
+            let search = if threads == 1 {
+                singlethreaded_method(method, start(), config)
+            } else {
+                multithreaded_method(method, start(), config)
+            };
+            assert_eq!(search.termination, Termination::NodeLimit);
+            let checkpoint = Checkpoint::load(&path).expect("Failed to load checkpoint.");
+            remove_file(&path).expect("Failed to remove checkpoint.");
+            let search =
+                resume_singlethreaded_method(method, checkpoint, SolverConfig::default()).unwrap();
+            assert_eq!(search.termination, Termination::Exhausted);
+            assert_eq!(search.best.score(), whole.best.score());
+            assert_eq!(roots(&search), roots(&whole));
+            if threads == 1 {
+                assert_eq!(search.nodes, whole.nodes);
+                assert_eq!((search.lookups, search.hits), (whole.lookups, whole.hits));
+            }
+        }
+    }
+
+    #[test]
+    fn resume_checks_store_and_threads() {
+        let method = Method::new(v_hs_eq, store_hs, store_npm_s_p, cmp_hpm, spm_intoiter_rev);
+        let whole = singlethreaded_method(method, start(), SolverConfig::default());
+        let config = interrupted("store", 2, 2500);
+        let path = config.checkpoint.clone().unwrap().path;
+        multithreaded_method(method, start(), config);
+        let checkpoint = Checkpoint::load(&path).expect("Failed to load checkpoint.");
+        assert_eq!(checkpoint.store(), "hash set");
+        // Saved with hash sets, so the tables can't be read back as vecs.
+        let vec_method =
+            Method::new(v_sorted_eq, store_v_sorted, store_npm_s_p, cmp_hpm, spm_intoiter_rev);
+        let error = resume_singlethreaded_method(vec_method, checkpoint, SolverConfig::default())
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        // No threads is taken as one, which takes over both saved workers.
+        let checkpoint = Checkpoint::load(&path).expect("Failed to load checkpoint.");
+        remove_file(&path).expect("Failed to remove checkpoint.");
+        let config = SolverConfig { threads: Some(0), ..SolverConfig::default() };
+        let search = resume_multithreaded_method(method, checkpoint, config).unwrap();
+        assert_eq!(search.termination, Termination::Exhausted);
+        assert_eq!(search.best.score(), whole.best.score());
+        assert_eq!(roots(&search), roots(&whole));
+    }
+
+    #[test]
+    fn failed_saves_leave_search_running() {
+        let method = Method::new(v_hs_eq, store_hs, store_npm_s_p, cmp_hpm, spm_intoiter_rev);
+        let whole = singlethreaded_method(method, start(), SolverConfig::default());
+        let path = temp_dir().join(format!("dots-missing-{}", process::id())).join("checkpoint");
+        let config = SolverConfig {
+            threads: Some(2),
+            checkpoint: Some(Checkpointing { path, interval: Duration::ZERO }),
+            ..SolverConfig::default()
+        };
+        for &multithreaded in [false, true].iter() {
+            let search = if multithreaded {
+                multithreaded_method(method, start(), config.clone())
+            } else {
+                singlethreaded_method(method, start(), config.clone())
+            };
+            assert_eq!(search.termination, Termination::Exhausted);
+            assert_eq!(search.best.score(), whole.best.score());
+            assert!(search.checkpoint_error().is_some());
+        }
+    }
+
+    #[test]
+    fn zero_threads_search_on_one() {
+        let method = Method::new(v_hs_eq, store_hs, store_npm_s_p, cmp_hpm, spm_intoiter_rev);
+        let whole = singlethreaded_method(method, start(), SolverConfig::default());
+        let config = SolverConfig { threads: Some(0), ..SolverConfig::default() };
+        assert_eq!(config.threads(), 1);
+        let search = multithreaded_method(method, start(), config);
+        assert_eq!(search.termination, Termination::Exhausted);
+        assert_eq!(search.best.score(), whole.best.score());
+        assert_eq!(roots(&search), roots(&whole));
+    }
+
+    #[test]
+    fn work_stealing_matches_single() {
+        let method = Method::new(v_hs_eq, store_hs, store_npm_s_p, cmp_hpm, spm_intoiter_rev);
+        let whole = singlethreaded_method(method, start(), SolverConfig::default());
+        // More workers than roots too, so some only ever get work by stealing it.
+        for &threads in [1, 2, 4, 8, 16].iter() {
+            let config = SolverConfig { threads: Some(threads), ..SolverConfig::default() };
+            let search = work_stealing_method(method, start(), config);
+            assert_eq!(search.termination, Termination::Exhausted, "{} threads", threads);
+            assert_eq!(search.best.score(), whole.best.score(), "{} threads", threads);
+            // The workers share their table, so between them they play every position once.
+            assert_eq!(search.nodes, whole.nodes, "{} threads", threads);
+        }
+    }
+
+    #[test]
+    fn cancel_stops_threaded_searches() {
+        let method = Method::new(v_hs_eq, store_hs, store_npm_s_p, cmp_hpm, spm_intoiter_rev);
+        for &name in ["threads", "rayon", "steal"].iter() {
+            // Maximising on the cross would take far longer than any test.
+            let cancel = Cancel::new();
+            let config = SolverConfig {
+                threads: Some(4),
+                cancel: Some(cancel.clone()),
+                ..SolverConfig::default()
+            };
+            let canceller = spawn(move || {
+                sleep(Duration::from_millis(200));
+                cancel.cancel();
+                Instant::now()
+            });
+            let search = match name {
+                "threads" => multithreaded_method(method, Game::new(), config),
+                "rayon" => multithreaded_rayon_method(method, Game::new(), config),
+                _ => work_stealing_method(method, Game::new(), config),
+            };
+            let stopped = Instant::now();
+            let cancelled = canceller.join().unwrap();
+            assert_eq!(search.termination, Termination::Cancelled, "{}", name);
+            assert!(stopped - cancelled < Duration::from_secs(2), "{} took too long", name);
+            assert!(search.best.score() > 0, "{}", name);
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use crate::methods::{TranspositionTable, Transpositions};
+use crate::point::Point;
+use crate::set::Set;
+use std::collections::VecDeque;
+use std::sync::{Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+// A piece of the search for an idle worker: either a whole root, or the subtree below the position
+// reached by playing `sets` from the start.
+#[derive(Clone, Debug)]
+pub(crate) enum Task {
+    Root(Point),
+    Subtree(Vec<Set>),
+}
+
+// Work shared between the workers of a work stealing search. Workers that run out of work wait
+// here, and busy workers hand them moves they haven't tried yet.
+#[derive(Debug)]
+pub(crate) struct Shared {
+    tasks: Mutex<Vec<Task>>,
+    wake: Condvar,
+    // Workers waiting for a task. Only changed with `tasks` locked.
+    idle: AtomicUsize,
+    workers: usize,
+    done: AtomicBool,
+}
+
+impl Shared {
+    pub(crate) fn new(mut tasks: Vec<Task>, workers: usize) -> Self {
+        // Tasks are taken from the back.
+        tasks.reverse();
+        Shared {
+            tasks: Mutex::new(tasks),
+            wake: Condvar::new(),
+            idle: AtomicUsize::new(0),
+            workers,
+            done: AtomicBool::new(false),
+        }
+    }
+
+    // Waits for the next task. Returns `None` once the search is over: either every worker is
+    // waiting and nothing is left to hand out, or `finish` was called.
+    pub(crate) fn next(&self) -> Option<Task> {
+        let mut tasks = self.tasks.lock().expect("Failed to get lock on shared tasks.");
+        loop {
+            if self.done.load(Ordering::SeqCst) {
+                return None;
+            }
+            if let Some(task) = tasks.pop() {
+                return Some(task);
+            }
+            if self.idle.fetch_add(1, Ordering::SeqCst) + 1 == self.workers {
+                self.done.store(true, Ordering::SeqCst);
+                self.wake.notify_all();
+                return None;
+            }
+            tasks = self.wake.wait(tasks).expect("Failed to get lock on shared tasks.");
+            self.idle.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    pub(crate) fn finish(&self) {
+        let _tasks = self.tasks.lock().expect("Failed to get lock on shared tasks.");
+        self.done.store(true, Ordering::SeqCst);
+        self.wake.notify_all();
+    }
+
+    // Hands one untried move to a waiting worker if there's a worker without a task. `siblings`
+    // is the calling worker's stack of untried moves, shallowest first, each with the number of
+    // sets of `sets` played before it. The move comes from the shallowest level that has one left,
+    // since that's where the biggest subtrees are.
+    #[inline]
+    pub(crate) fn donate(&self, siblings: &mut [(usize, VecDeque<(Set, Point)>)], sets: &[Set]) {
+        if self.idle.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+        let mut tasks = self.tasks.lock().expect("Failed to get lock on shared tasks.");
+        if tasks.len() >= self.idle.load(Ordering::SeqCst) {
+            return;
+        }
+        let level = siblings.iter_mut().find(|(_, moves)| !moves.is_empty());
+        if let Some((played, moves)) = level {
+            let (set, _) = moves.pop_back().unwrap();
+            let mut task = sets[..*played].to_vec();
+            task.push(set);
+            tasks.push(Task::Subtree(task));
+            self.wake.notify_one();
+        }
+    }
+}
+
+// Shards of `SharedTable`, each locked on its own so that workers rarely wait for each other.
+const SHARDS: usize = 64;
+
+// The transposition table of a work stealing search, shared by every worker so that a worker taking
+// over a subtree skips the positions the worker that gave it away has already seen, and the whole
+// search plays every position once however many workers it has.
+#[derive(Debug)]
+pub(crate) struct SharedTable<MoveSet> {
+    shards: Vec<Mutex<TranspositionTable<MoveSet>>>,
+}
+
+impl<MoveSet> SharedTable<MoveSet> {
+    pub(crate) fn new() -> Self {
+        SharedTable {
+            shards: (0..SHARDS).map(|_| Mutex::new(TranspositionTable::default())).collect(),
+        }
+    }
+}
+
+impl<MoveSet> Transpositions<MoveSet> for &SharedTable<MoveSet> {
+    #[inline]
+    fn bucket<R>(&mut self, key: u64, f: impl FnOnce(&mut Vec<MoveSet>) -> R) -> R {
+        let mut shard = self.shards[(key % SHARDS as u64) as usize]
+            .lock()
+            .expect("Failed to get lock on shared gamestates.");
+        f(shard.entry(key).or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::set::Direction;
+    use std::thread::{scope, yield_now};
+
+    fn roots(count: i16) -> Vec<Task> {
+        (0..count).map(|x| Task::Root(Point::new(x, 0))).collect()
+    }
+
+    fn idle(shared: &Shared) -> usize {
+        shared.idle.load(Ordering::SeqCst)
+    }
+
+    #[test]
+    fn ends_with_more_workers_than_tasks() {
+        let shared = Shared::new(roots(2), 5);
+        let taken = scope(|s| {
+            let workers = (0..5)
+                .map(|_| s.spawn(|| {
+                    let mut taken = 0;
+                    while shared.next().is_some() {
+                        taken += 1;
+                    }
+                    taken
+                }))
+                .collect::<Vec<_>>();
+            workers.into_iter().map(|worker| worker.join().unwrap()).sum::<usize>()
+        });
+        assert_eq!(taken, 2);
+    }
+
+    #[test]
+    fn tasks_come_out_in_order() {
+        let shared = Shared::new(roots(3), 1);
+        for x in 0..3 {
+            match shared.next() {
+                Some(Task::Root(point)) => assert_eq!(point, Point::new(x, 0)),
+                task => panic!("Expected root {}, got {:?}", x, task),
+            }
+        }
+        assert!(shared.next().is_none());
+    }
+
+    #[test]
+    fn donates_shallowest_move_to_idle_worker() {
+        let set = |x| Set::new(Point::new(x, 0), Direction::V, 0, 5);
+        let point = Point::new(0, 0);
+        let sets = vec![set(0), set(1), set(2)];
+        let mut siblings = vec![
+            (1, VecDeque::new()),
+            (2, vec![(set(10), point), (set(11), point)].into_iter().collect()),
+            (3, vec![(set(20), point)].into_iter().collect()),
+        ];
+        let shared = Shared::new(Vec::new(), 2);
+        // Nobody is waiting, so nothing is handed out.
+        shared.donate(&mut siblings, &sets);
+        assert_eq!(siblings[1].1.len(), 2);
+        let task = scope(|s| {
+            let thief = s.spawn(|| shared.next());
+            while idle(&shared) == 0 {
+                yield_now();
+            }
+            shared.donate(&mut siblings, &sets);
+            thief.join().unwrap()
+        });
+        match task {
+            Some(Task::Subtree(moves)) => assert_eq!(moves, vec![set(0), set(1), set(11)]),
+            task => panic!("Expected a subtree, got {:?}", task),
+        }
+        assert_eq!(siblings[1].1.iter().map(|&(set, _)| set).collect::<Vec<_>>(), [set(10)]);
+        assert_eq!(siblings[2].1.len(), 1);
+        assert_eq!(idle(&shared), 0);
+        shared.finish();
+        assert!(shared.next().is_none());
+    }
+}
@@ -0,0 +1,243 @@
+use crate::game::Game;
+use crate::point::Point;
+use crate::set::{Direction, Set};
+
+// The 8 symmetries of the square grid, as maps of a vector from the centre.
+#[derive(Eq, PartialEq, Copy, Clone, Hash, Debug)]
+pub enum Dihedral {
+    Identity,
+    // Anticlockwise rotations
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    // x -> -x
+    FlipX,
+    // y -> -y
+    FlipY,
+    // x <-> y
+    Transpose,
+    // x <-> -y
+    AntiTranspose,
+}
+
+pub const DIHEDRALS: [Dihedral; 8] = [
+    Dihedral::Identity,
+    Dihedral::Rotate90,
+    Dihedral::Rotate180,
+    Dihedral::Rotate270,
+    Dihedral::FlipX,
+    Dihedral::FlipY,
+    Dihedral::Transpose,
+    Dihedral::AntiTranspose,
+];
+
+impl Dihedral {
+    #[inline]
+    pub fn apply(self, x: i32, y: i32) -> (i32, i32) {
+        match self {
+            Dihedral::Identity => (x, y),
+            Dihedral::Rotate90 => (-y, x),
+            Dihedral::Rotate180 => (-x, -y),
+            Dihedral::Rotate270 => (y, -x),
+            Dihedral::FlipX => (-x, y),
+            Dihedral::FlipY => (x, -y),
+            Dihedral::Transpose => (y, x),
+            Dihedral::AntiTranspose => (-y, -x),
+        }
+    }
+}
+
+// A symmetry of the grid about a centre. The centre is kept in half units so that it can sit
+// between points, like the centre of the standard cross does.
+#[derive(Eq, PartialEq, Copy, Clone, Hash, Debug)]
+pub struct Symmetry {
+    pub(crate) dihedral: Dihedral,
+    pub(crate) centre_x2: i32,
+    pub(crate) centre_y2: i32,
+}
+
+impl Symmetry {
+    pub fn dihedral(&self) -> Dihedral {
+        self.dihedral
+    }
+
+    // Image of `point` in half units, which is only on the grid if both coordinates are even.
+    #[inline]
+    fn apply_x2(&self, point: Point) -> (i32, i32) {
+        let (x, y) = self.dihedral.apply(
+            2 * point.x as i32 - self.centre_x2,
+            2 * point.y as i32 - self.centre_y2
+        );
+        (x + self.centre_x2, y + self.centre_y2)
+    }
+}
+
+// The symmetries that map `points` onto themselves, starting with the identity. Any such symmetry
+// maps the bounding box onto itself, so its centre is the centre of the bounding box.
+pub fn symmetries_of(points: &[Point]) -> Vec<Symmetry> {
+    let (min_x, max_x, min_y, max_y) = points.iter().fold(
+        (i16::MAX, i16::MIN, i16::MAX, i16::MIN),
+        |(min_x, max_x, min_y, max_y), point| {
+            (min_x.min(point.x), max_x.max(point.x), min_y.min(point.y), max_y.max(point.y))
+        }
+    );
+    let (centre_x2, centre_y2) = if points.is_empty() {
+        (0, 0)
+    } else {
+        (min_x as i32 + max_x as i32, min_y as i32 + max_y as i32)
+    };
+    DIHEDRALS
+        .iter()
+        .map(|&dihedral| Symmetry { dihedral, centre_x2, centre_y2 })
+        .filter(|symmetry| {
+            points.iter().all(|&point| {
+                let (x, y) = symmetry.apply_x2(point);
+                x % 2 == 0 && y % 2 == 0 && points.contains(&Point::new(x as i16 / 2, y as i16 / 2))
+            })
+        })
+        .collect()
+}
+
+impl Point {
+    #[inline]
+    pub fn transform(&self, symmetry: Symmetry) -> Point {
+        let (x, y) = symmetry.apply_x2(*self);
+        Point::new((x / 2) as i16, (y / 2) as i16)
+    }
+}
+
+impl Direction {
+    #[inline]
+    pub fn transform(&self, dihedral: Dihedral) -> Direction {
+        let (x, y) = self.single_step();
+        match dihedral.apply(x as i32, y as i32) {
+            (_, 0) => Direction::H,
+            (0, _) => Direction::V,
+            (x, y) if x == y => Direction::SP,
+            _ => Direction::SN,
+        }
+    }
+}
+
+impl Set {
+    #[inline]
+    pub fn transform(&self, symmetry: Symmetry) -> Set {
+        let start = self.start_point().transform(symmetry);
+        let end = self.end_point().transform(symmetry);
+        let direction = self.direction.transform(symmetry.dihedral);
+        // The image runs from `end` to `start` if the direction's step got flipped.
+        let (step_x, step_y) = direction.single_step();
+        let forwards = (end.x - start.x) * step_x + (end.y - start.y) * step_y > 0;
+        let start = if forwards { start } else { end };
+        Set {
+            start_x: start.x,
+            start_y: start.y,
+            direction,
+            length: self.length,
+        }
+    }
+}
+
+impl Game {
+    // Symmetries of the starting points, starting with the identity. Positions that one of these
+    // maps onto each other are the same position for the search.
+    pub fn symmetries(&self) -> &[Symmetry] {
+        &self.symmetries
+    }
+
+    // Hash of the position that's the same for every image of it under `symmetries`.
+    #[inline]
+    pub fn canonical_zobrist(&self) -> u64 {
+        self.zobrists[..self.symmetries.len()].iter().cloned().min().unwrap_or(0)
+    }
+
+    // The sets of the position sorted by `Set::packed`, mapped by the symmetry whose image has the
+    // smallest hash, the one `canonical_zobrist` picks. Symmetric positions have the same canonical
+    // form, barring hash collisions, which at worst make a transposition go unnoticed.
+    pub fn canonical(&self) -> Vec<Set> {
+        let symmetry = self
+            .symmetries
+            .iter()
+            .zip(self.zobrists.iter())
+            .min_by_key(|&(_, &zobrist)| zobrist)
+            .map(|(&symmetry, _)| symmetry);
+        let mut sets = match symmetry {
+            Some(symmetry) => self.sets.iter().map(|set| set.transform(symmetry)).collect(),
+            None => self.sets.clone(),
+        };
+        sets.sort_unstable_by_key(Set::packed);
+        sets
+    }
+
+    // The symmetries of the start that also map the sets played so far onto themselves, judged by
+    // their hashes.
+    pub fn position_symmetries(&self) -> impl Iterator<Item = Symmetry> + '_ {
+        let zobrist = self.zobrist();
+        self.symmetries
+            .iter()
+            .zip(self.zobrists.iter())
+            .filter(move |&(_, &image)| image == zobrist)
+            .map(|(&symmetry, _)| symmetry)
+    }
+
+    // The first starting point of each orbit under `position_symmetries`. Roots in the same orbit
+    // lead to symmetric searches, so the solvers only search these.
+    pub fn root_points(&self) -> Vec<Point> {
+        let symmetries = self.position_symmetries().collect::<Vec<_>>();
+        let mut roots: Vec<Point> = Vec::new();
+        for &point in self.starting_points.iter() {
+            if !symmetries.iter().any(|&symmetry| roots.contains(&point.transform(symmetry))) {
+                roots.push(point);
+            }
+        }
+        roots
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::methods::{
+        cmp_hpm,
+        Method,
+        spm_intoiter_rev,
+        store_hs,
+        store_npm_s_p,
+        v_hs_eq
+    };
+    use crate::rules::Rules;
+    use crate::solvers::{singlethreaded_method, SolverConfig};
+
+    // An L of four points, which no symmetry but the identity maps onto itself.
+    fn asymmetric() -> Game {
+        let points = [Point::new(0, 0), Point::new(1, 0), Point::new(2, 0), Point::new(0, 1)];
+        Game::from_points(points.iter().cloned(), Rules::default())
+    }
+
+    #[test]
+    fn cross_has_eight_symmetries_and_five_root_orbits() {
+        let game = Game::new();
+        assert_eq!(game.symmetries().len(), 8);
+        assert_eq!(game.symmetries()[0].dihedral(), Dihedral::Identity);
+        assert_eq!(game.root_points().len(), 5);
+    }
+
+    #[test]
+    fn asymmetric_start_has_only_identity() {
+        let game = asymmetric();
+        assert_eq!(game.symmetries().len(), 1);
+        assert_eq!(game.symmetries()[0].dihedral(), Dihedral::Identity);
+        assert_eq!(game.root_points(), game.starting_points());
+    }
+
+    #[test]
+    fn search_skips_roots_outside_their_orbits() {
+        let method = Method::new(v_hs_eq, store_hs, store_npm_s_p, cmp_hpm, spm_intoiter_rev);
+        for game in [Game::new(), asymmetric()].iter() {
+            let config = SolverConfig { node_limit: Some(1), ..SolverConfig::default() };
+            let search = singlethreaded_method(method, game.clone(), config);
+            let skipped = game.starting_points().len() - game.root_points().len();
+            assert_eq!(search.skipped_roots(), skipped);
+        }
+    }
+}